@@ -1,468 +1,1204 @@
-use std::convert::TryFrom;
+use std::fmt;
 
+use num_integer::Integer;
+use num_traits::{
+    CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, FromPrimitive, One, Signed, ToPrimitive, Zero,
+};
+
+use parse::color::Color;
+use parse::region::RegionId;
+
+use crate::options::{
+    DivisionRounding, EndOfInputPolicy, InvalidNumberPolicy, NegativeRollPolicy, NumberInput,
+    OverflowPolicy,
+};
 use crate::state::State;
+use crate::step::ExitReason;
+use crate::stream::PietInput;
+use crate::value::{value, Value};
 
-/// Selects a command based on the change in hue/lightness between two regions and executes it.
+/// The operations that a Piet program can perform, selected by the change in hue/lightness between two regions.
 ///
 /// | hue/lightness change | 0    | 1        | 2      | 3       | 4         | 5          |
 /// |----------------------|------|----------|--------|---------|-----------|------------|
 /// | 0                    |      | add      | divide | greater | duplicate | in_char    |
 /// | 1                    | push | subtract | modulo | pointer | roll      | out_number |
 /// | 2                    | pop  | multiply | not    | switch  | in_number | out_char   |
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    Push,
+    Pop,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Not,
+    Greater,
+    Pointer,
+    Switch,
+    Duplicate,
+    Roll,
+    InNumber,
+    InChar,
+    OutNumber,
+    OutChar,
+}
+
+impl Command {
+    /// Every command in order of increasing hue change and then lightness change.
+    pub fn all() -> impl Iterator<Item = Command> {
+        vec![
+            Command::Push,
+            Command::Pop,
+            Command::Add,
+            Command::Subtract,
+            Command::Multiply,
+            Command::Divide,
+            Command::Modulo,
+            Command::Not,
+            Command::Greater,
+            Command::Pointer,
+            Command::Switch,
+            Command::Duplicate,
+            Command::Roll,
+            Command::InNumber,
+            Command::InChar,
+            Command::OutNumber,
+            Command::OutChar,
+        ]
+        .into_iter()
+    }
+
+    /// The command performed by a given change in hue and lightness, if there is one.
+    pub fn from_delta(delta_hue: u8, delta_lightness: u8) -> Option<Command> {
+        match (delta_hue, delta_lightness) {
+            (0, 1) => Some(Command::Push),
+            (0, 2) => Some(Command::Pop),
+            (1, 0) => Some(Command::Add),
+            (1, 1) => Some(Command::Subtract),
+            (1, 2) => Some(Command::Multiply),
+            (2, 0) => Some(Command::Divide),
+            (2, 1) => Some(Command::Modulo),
+            (2, 2) => Some(Command::Not),
+            (3, 0) => Some(Command::Greater),
+            (3, 1) => Some(Command::Pointer),
+            (3, 2) => Some(Command::Switch),
+            (4, 0) => Some(Command::Duplicate),
+            (4, 1) => Some(Command::Roll),
+            (4, 2) => Some(Command::InNumber),
+            (5, 0) => Some(Command::InChar),
+            (5, 1) => Some(Command::OutNumber),
+            (5, 2) => Some(Command::OutChar),
+            _ => None,
+        }
+    }
+
+    /// The (hue, lightness) change that performs the command.
+    pub fn delta(&self) -> (u8, u8) {
+        match self {
+            Command::Push => (0, 1),
+            Command::Pop => (0, 2),
+            Command::Add => (1, 0),
+            Command::Subtract => (1, 1),
+            Command::Multiply => (1, 2),
+            Command::Divide => (2, 0),
+            Command::Modulo => (2, 1),
+            Command::Not => (2, 2),
+            Command::Greater => (3, 0),
+            Command::Pointer => (3, 1),
+            Command::Switch => (3, 2),
+            Command::Duplicate => (4, 0),
+            Command::Roll => (4, 1),
+            Command::InNumber => (4, 2),
+            Command::InChar => (5, 0),
+            Command::OutNumber => (5, 1),
+            Command::OutChar => (5, 2),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    /// Writes the command's name as it appears in the table above, e.g. `push` or `in_number`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Command::Push => "push",
+            Command::Pop => "pop",
+            Command::Add => "add",
+            Command::Subtract => "subtract",
+            Command::Multiply => "multiply",
+            Command::Divide => "divide",
+            Command::Modulo => "modulo",
+            Command::Not => "not",
+            Command::Greater => "greater",
+            Command::Pointer => "pointer",
+            Command::Switch => "switch",
+            Command::Duplicate => "duplicate",
+            Command::Roll => "roll",
+            Command::InNumber => "in_number",
+            Command::InChar => "in_char",
+            Command::OutNumber => "out_number",
+            Command::OutChar => "out_char",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Looks up colors by the command that they perform.
+///
+/// This would be an inherent `Color::next_for`, but inherent methods can only be defined in the crate that owns the type
+/// and `parse` can't refer to `Command` without depending on this crate, which already depends on it.
+pub trait NextColor {
+    /// The color that performs `command` when the pointer moves into it from this color.
+    /// Returns `None` for black and white, which never perform commands.
+    fn next_for(&self, command: Command) -> Option<Color>;
+}
+
+impl NextColor for Color {
+    fn next_for(&self, command: Command) -> Option<Color> {
+        let (delta_hue, delta_lightness) = command.delta();
+        self.shift(delta_hue, delta_lightness)
+    }
+}
+
+/// Why a command was ignored rather than carried out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Skip {
+    /// There weren't enough values on the stack.
+    StackUnderflow,
+    /// The command would have divided by zero.
+    DivideByZero,
+    /// The roll depth was negative or deeper than the stack.
+    InvalidRoll,
+    /// There was no input left to read.
+    NoInput,
+    /// The input couldn't be read as the expected type.
+    InvalidInput,
+    /// The value isn't a valid character.
+    InvalidChar,
+    /// The output couldn't be written to.
+    OutputFailed,
+    /// The result was outside the range of a stack value.
+    Overflow,
+}
+
+/// Input read or output written by a command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Io {
+    /// A value was read and pushed on to the stack.
+    Read(Value),
+    /// Text was written.
+    Wrote(String),
+}
+
+/// The result of executing a command: any I/O that it performed, or the reason that it was ignored.
+pub type Outcome = Result<Option<Io>, Skip>;
+
+/// Executes a command.
 ///
 /// Any operations which cannot be performed (such as popping values when not enough are on the stack) are simply ignored, and processing continues with the next command.
-pub fn execute(state: &mut State, delta_hue: u8, delta_lightness: u8, current_region_size: usize) {
-    match (delta_hue, delta_lightness) {
-        (0, 0) => (),
-        (0, 1) => push(state, current_region_size),
-        (0, 2) => pop(state),
-        (1, 0) => add(state),
-        (1, 1) => subtract(state),
-        (1, 2) => multiply(state),
-        (2, 0) => divide(state),
-        (2, 1) => modulo(state),
-        (2, 2) => not(state),
-        (3, 0) => greater(state),
-        (3, 1) => pointer(state),
-        (3, 2) => switch(state),
-        (4, 0) => duplicate(state),
-        (4, 1) => roll(state),
-        (4, 2) => in_number(state),
-        (5, 0) => in_char(state),
-        (5, 1) => out_number(state),
-        (5, 2) => out_char(state),
-        _ => panic!(
-            "Unexpected hue ({}) / lightness ({}) change",
-            delta_hue, delta_lightness
-        ),
+pub(crate) fn execute(
+    state: &mut State,
+    command: Command,
+    current_region: RegionId,
+    current_region_size: usize,
+) -> Outcome {
+    let outcome = match command {
+        Command::Push => push(state, current_region_size),
+        Command::Pop => pop(state),
+        Command::Add => add(state),
+        Command::Subtract => subtract(state),
+        Command::Multiply => multiply(state),
+        Command::Divide => divide(state),
+        Command::Modulo => modulo(state),
+        Command::Not => not(state),
+        Command::Greater => greater(state),
+        Command::Pointer => pointer(state),
+        Command::Switch => switch(state),
+        Command::Duplicate => duplicate(state),
+        Command::Roll => roll(state),
+        Command::InNumber => in_number(state),
+        Command::InChar => in_char(state),
+        Command::OutNumber => out_number(state),
+        Command::OutChar => out_char(state),
     };
+    if outcome == Err(Skip::Overflow) && state.overflow == OverflowPolicy::Halt {
+        state.stopped = Some(ExitReason::Overflow {
+            region: current_region,
+            command,
+        });
+    }
+    outcome
+}
+
+/// Pops the top two values off the stack, returning (top, second top), or ignores the command if there aren't two values.
+fn pop_two(state: &mut State) -> Result<(Value, Value), Skip> {
+    if state.stack.len() >= 2 {
+        let one = state.stack.pop().unwrap();
+        let two = state.stack.pop().unwrap();
+        Ok((one, two))
+    } else {
+        Err(Skip::StackUnderflow)
+    }
+}
+
+/// Pops the top value off the stack, or ignores the command if the stack is empty.
+fn pop_one(state: &mut State) -> Result<Value, Skip> {
+    state.stack.pop().ok_or(Skip::StackUnderflow)
 }
 
 /// Pushes the value of the colour block just exited on to the stack.
 /// Note that values of colour blocks are not automatically pushed on to the stack - this push operation must be explicitly carried out.
-fn push(state: &mut State, current_region_size: usize) {
-    state.stack.push(current_region_size as isize);
+fn push(state: &mut State, current_region_size: usize) -> Outcome {
+    state
+        .stack
+        .push(Value::from_usize(current_region_size).unwrap());
+    Ok(None)
 }
 
 /// Pops the top value off the stack and discards it.
-fn pop(state: &mut State) {
-    state.stack.pop();
+fn pop(state: &mut State) -> Outcome {
+    pop_one(state)?;
+    Ok(None)
 }
 
 /// Pops the top two values off the stack, adds them, and pushes the result back on the stack.
-fn add(state: &mut State) {
-    if state.stack.len() >= 2 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(one + two);
-    }
+fn add(state: &mut State) -> Outcome {
+    arithmetic(state, Arithmetic::Add)
 }
 
 /// Pops the top two values off the stack, calculates the second top value minus the top value, and pushes the result back on the stack.
-fn subtract(state: &mut State) {
-    if state.stack.len() >= 2 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(two - one);
-    }
+fn subtract(state: &mut State) -> Outcome {
+    arithmetic(state, Arithmetic::Subtract)
 }
 
 /// Pops the top two values off the stack, multiplies them, and pushes the result back on the stack.
-fn multiply(state: &mut State) {
-    if state.stack.len() >= 2 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(two * one);
-    }
+fn multiply(state: &mut State) -> Outcome {
+    arithmetic(state, Arithmetic::Multiply)
 }
 
 /// Pops the top two values off the stack, calculates the integer division of the second top value by the top value, and pushes the result back on the stack.
 /// If a divide by zero occurs, it is handled as an implementation-dependent error, though simply ignoring the command is recommended.
-fn divide(state: &mut State) {
-    if state.stack.len() >= 2 && state.stack.last().unwrap() != &0 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(two / one);
+/// Results that aren't whole numbers are rounded according to the `DivisionRounding`.
+fn divide(state: &mut State) -> Outcome {
+    if state.stack.len() >= 2 && state.stack.last().unwrap().is_zero() {
+        return Err(Skip::DivideByZero);
     }
+    let rounding = state.dialect.division;
+    arithmetic(state, Arithmetic::Divide(rounding))
+}
+
+/// The arithmetic operations which can overflow.
+#[derive(Copy, Clone)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide(DivisionRounding),
+}
+
+impl Arithmetic {
+    /// Applies the operation to the second top and top values, or returns `None` if it overflows.
+    fn checked(self, two: &Value, one: &Value) -> Option<Value> {
+        match self {
+            Arithmetic::Add => CheckedAdd::checked_add(two, one),
+            Arithmetic::Subtract => CheckedSub::checked_sub(two, one),
+            Arithmetic::Multiply => CheckedMul::checked_mul(two, one),
+            Arithmetic::Divide(rounding) => {
+                CheckedDiv::checked_div(two, one).map(|quotient| match rounding {
+                    DivisionRounding::Floor => Integer::div_floor(two, one),
+                    DivisionRounding::Truncate => quotient,
+                })
+            }
+        }
+    }
+
+    /// Applies an operation which overflowed, either wrapping or saturating the result.
+    #[cfg(not(feature = "bigint"))]
+    fn overflowed(self, two: Value, one: Value, saturate: bool) -> Value {
+        // The only division that overflows is exact, so rounding doesn't matter
+        match (self, saturate) {
+            (Arithmetic::Add, false) => two.wrapping_add(one),
+            (Arithmetic::Add, true) => two.saturating_add(one),
+            (Arithmetic::Subtract, false) => two.wrapping_sub(one),
+            (Arithmetic::Subtract, true) => two.saturating_sub(one),
+            (Arithmetic::Multiply, false) => two.wrapping_mul(one),
+            (Arithmetic::Multiply, true) => two.saturating_mul(one),
+            (Arithmetic::Divide(_), false) => two.wrapping_div(one),
+            (Arithmetic::Divide(_), true) => two.saturating_div(one),
+        }
+    }
+
+    /// Applies an operation which overflowed, either wrapping or saturating the result.
+    #[cfg(feature = "bigint")]
+    fn overflowed(self, _two: Value, _one: Value, _saturate: bool) -> Value {
+        unreachable!("arbitrary-precision values never overflow")
+    }
+}
+
+/// Pops the top two values off the stack, applies an operation to them, and pushes the result back on the stack.
+/// If the result overflows, what happens depends on the `OverflowPolicy`.
+fn arithmetic(state: &mut State, operation: Arithmetic) -> Outcome {
+    let (one, two) = pop_two(state)?;
+    let result = match operation.checked(&two, &one) {
+        Some(result) => result,
+        None => match state.overflow {
+            OverflowPolicy::Wrap => operation.overflowed(two, one, false),
+            OverflowPolicy::Saturate => operation.overflowed(two, one, true),
+            // Halting is handled by `execute` since it knows which region the command came from
+            OverflowPolicy::Ignore | OverflowPolicy::Halt => {
+                state.stack.push(two);
+                state.stack.push(one);
+                return Err(Skip::Overflow);
+            }
+        },
+    };
+    state.stack.push(result);
+    Ok(None)
 }
 
 /// Pops the top two values off the stack, calculates the second top value modulo the top value, and pushes the result back on the stack.
-/// The result has the same sign as the divisor (the top value).
+/// The result has the same sign as the divisor (the top value), or as the dividend with `DivisionRounding::Truncate` so that it always agrees with `divide`.
 /// If the top value is zero, this is a divide by zero error, which is handled as an implementation-dependent error, though simply ignoring the command is recommended.
-fn modulo(state: &mut State) {
-    if state.stack.len() >= 2 && state.stack.last().unwrap() != &0 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(two % one);
+fn modulo(state: &mut State) -> Outcome {
+    if state.stack.len() >= 2 && state.stack.last().unwrap().is_zero() {
+        return Err(Skip::DivideByZero);
+    }
+    let (one, two) = pop_two(state)?;
+    // Everything is a multiple of -1, and checking first avoids overflowing when the dividend is the smallest integer
+    if one == -Value::one() {
+        state.stack.push(Value::zero());
+    } else {
+        state.stack.push(match state.dialect.division {
+            DivisionRounding::Floor => two.mod_floor(&one),
+            DivisionRounding::Truncate => Integer::div_rem(&two, &one).1,
+        });
     }
+    Ok(None)
 }
 
 /// Replaces the top value of the stack with 0 if it is non-zero, and 1 if it is zero.
-fn not(state: &mut State) {
-    if let Some(top) = state.stack.pop() {
-        state.stack.push(if top == 0 { 1 } else { 0 });
-    }
+fn not(state: &mut State) -> Outcome {
+    let top = pop_one(state)?;
+    state.stack.push(if top.is_zero() {
+        Value::one()
+    } else {
+        Value::zero()
+    });
+    Ok(None)
 }
 
 /// Pops the top two values off the stack, and pushes 1 on to the stack if the second top value is greater than the top value, and pushes 0 if it is not greater.
-fn greater(state: &mut State) {
-    if state.stack.len() >= 2 {
-        let one = state.stack.pop().unwrap();
-        let two = state.stack.pop().unwrap();
-        state.stack.push(if two > one { 1 } else { 0 });
-    }
+fn greater(state: &mut State) -> Outcome {
+    let (one, two) = pop_two(state)?;
+    state.stack.push(if two > one {
+        Value::one()
+    } else {
+        Value::zero()
+    });
+    Ok(None)
 }
 
 /// Pops the top value off the stack and rotates the DP clockwise that many steps (anticlockwise if negative).
-fn pointer(state: &mut State) {
-    if let Some(top) = state.stack.pop() {
-        let clockwise_steps = {
-            let absolute_steps = top % 4;
-            if absolute_steps >= 0 {
-                absolute_steps
-            } else {
-                4 + absolute_steps
-            }
-        };
-        for _ in 0..clockwise_steps {
-            state.direction = state.direction.next();
-        }
+fn pointer(state: &mut State) -> Outcome {
+    let top = pop_one(state)?;
+    // Four steps are a full rotation, and flooring turns anticlockwise steps into the equivalent clockwise ones
+    let clockwise_steps = top.mod_floor(&value(4)).to_usize().unwrap();
+    for _ in 0..clockwise_steps {
+        state.direction = state.direction.next();
     }
+    Ok(None)
 }
 
 /// Pops the top value off the stack and toggles the CC that many times (the absolute value of that many times if negative).
-fn switch(state: &mut State) {
-    if let Some(top) = state.stack.pop() {
-        let steps = (top % 2).abs();
-        for _ in 0..steps {
-            state.chooser = state.chooser.next();
-        }
+fn switch(state: &mut State) -> Outcome {
+    let top = pop_one(state)?;
+    // Toggling twice has no effect, so only odd values change anything
+    if top.is_odd() {
+        state.chooser = state.chooser.next();
     }
+    Ok(None)
 }
 
 /// Pushes a copy of the top value on the stack on to the stack.
-fn duplicate(state: &mut State) {
-    if let Some(top) = state.stack.pop() {
-        state.stack.push(top);
-        state.stack.push(top);
-    }
+fn duplicate(state: &mut State) -> Outcome {
+    let top = state
+        .stack
+        .len()
+        .checked_sub(1)
+        .ok_or(Skip::StackUnderflow)?;
+    state.stack.extend_from_within(top..);
+    Ok(None)
 }
 
 /// Pops the top two values off the stack and "rolls" the remaining stack entries to a depth equal to the second value popped, by a number of rolls equal to the first value popped.
 /// A single roll to depth n is defined as burying the top value on the stack n deep and bringing all values above it up by 1 place.
 /// A negative number of rolls rolls in the opposite direction.
-/// A negative depth is an error and the command is ignored.
+/// A negative depth is an error which is handled according to the `NegativeRollPolicy`.
 /// If a roll is greater than an implementation-dependent maximum stack depth, it is handled as an implementation-dependent error, though simply ignoring the command is recommended.
-fn roll(state: &mut State) {
-    let stack_size = state.stack.len();
-    let maybe_final_stack_size = stack_size.checked_sub(2);
-
+fn roll(state: &mut State) -> Outcome {
     // Only roll if:
     //  - we actually have at least two elements to pop off the stack
     //  - the roll depth is positive
     //  - the roll depth is not greater than the stack size after popping off the top two elements
-    if let Some(final_stack_size) = maybe_final_stack_size {
-        // TODO: this is sloppy and full of redundant checks, it'll be easy to clean up after if let chains are added
-        if let Some(depth) = state
-            .stack
-            .get(final_stack_size)
-            .and_then(|depth| usize::try_from(*depth).ok())
-            .and_then(|depth| {
-                if depth <= final_stack_size {
-                    Some(depth)
-                } else {
-                    None
-                }
-            })
-        {
-            let turns = state.stack.pop().unwrap();
-            let _depth = state.stack.pop().unwrap();
-
-            if turns >= 0 {
-                for _ in 0..turns {
-                    let top = state.stack.pop().unwrap();
-                    state.stack.insert(final_stack_size - depth, top);
-                }
-            } else {
-                for _ in turns..0 {
-                    let bottom = state.stack.remove(final_stack_size - depth);
-                    state.stack.push(bottom);
-                }
+    let final_stack_size = state
+        .stack
+        .len()
+        .checked_sub(2)
+        .ok_or(Skip::StackUnderflow)?;
+    let depth = &state.stack[final_stack_size];
+    if depth.is_negative() && state.dialect.negative_roll == NegativeRollPolicy::Discard {
+        pop_two(state)?;
+        return Err(Skip::InvalidRoll);
+    }
+    let depth = depth
+        .to_usize()
+        .filter(|depth| depth <= &final_stack_size)
+        .ok_or(Skip::InvalidRoll)?;
+    let (turns, _depth) = pop_two(state)?;
+
+    if depth > 0 {
+        // Rolling `depth` times is a full rotation, and flooring turns negative rolls into the equivalent positive ones
+        let turns = turns.mod_floor(&Value::from_usize(depth).unwrap());
+        state.stack[final_stack_size - depth..].rotate_right(turns.to_usize().unwrap());
+    }
+    Ok(None)
+}
+
+/// Reads a value from STDIN as either a number or character, depending on the particular incarnation of this command and pushes it on to the stack.
+/// If no input is waiting on STDIN, what happens depends on the `EndOfInputPolicy`.
+/// If an integer read does not receive an integer value, what happens depends on the `InvalidNumberPolicy`.
+///
+/// Like npiet, leading whitespace is skipped and then an optional sign and a run of digits are read.
+/// Input after the number is left for the next read, but when there's no number the character that should have started it is consumed
+/// (along with any sign before it) so that a program reading in a loop always makes progress.
+/// A sign at the very end of the input is invalid rather than the end of input, since there was input left when the command ran.
+/// With `NumberInput::Digit`, a single character is read as a digit instead.
+fn in_number(state: &mut State) -> Outcome {
+    let input = &mut state.input;
+    if state.dialect.number_input == NumberInput::Integer {
+        while input.peek_char().is_some_and(char::is_whitespace) {
+            input.read_char();
+        }
+    }
+    if input.peek_char().is_none() {
+        return end_of_input(state);
+    }
+
+    let number = match state.dialect.number_input {
+        NumberInput::Integer => read_number(input),
+        NumberInput::Digit => input
+            .read_char()
+            .and_then(|char| char.to_digit(10))
+            .and_then(Value::from_u32),
+    };
+    let number = match number {
+        Some(number) => number,
+        None => match state.dialect.invalid_number {
+            InvalidNumberPolicy::Ignore => return Err(Skip::InvalidInput),
+            InvalidNumberPolicy::Zero => Value::zero(),
+            InvalidNumberPolicy::Halt => {
+                state.stopped = Some(ExitReason::InvalidInput);
+                return Err(Skip::InvalidInput);
             }
+        },
+    };
+    push_read(state, number)
+}
+
+/// Handles an input command when there's no input left.
+fn end_of_input(state: &mut State) -> Outcome {
+    match state.dialect.end_of_input {
+        EndOfInputPolicy::Ignore => Err(Skip::NoInput),
+        EndOfInputPolicy::Push(pushed) => push_read(state, value(pushed)),
+        EndOfInputPolicy::Halt => {
+            state.stopped = Some(ExitReason::InputExhausted);
+            Err(Skip::NoInput)
         }
     }
 }
 
-/// Reads a value from STDIN as either a number or character, depending on the particular incarnation of this command and pushes it on to the stack.
-/// If no input is waiting on STDIN, this is an error and the command is ignored.
-/// If an integer read does not receive an integer value, this is an error and the command is ignored.
-fn in_number(state: &mut State) {
-    if let Some(digit) = state.stdin.pop().and_then(|char| char.to_digit(10)) {
-        state.stack.push(digit as isize)
+/// Reads an optional sign followed by a run of digits, or `None` if there are no digits or the number is too large.
+/// If there are no digits then the character in their place is consumed too.
+fn read_number(input: &mut dyn PietInput) -> Option<Value> {
+    let negative = match input.peek_char() {
+        Some(sign @ ('+' | '-')) => {
+            input.read_char();
+            sign == '-'
+        }
+        _ => false,
+    };
+
+    let mut number = Some(Value::zero());
+    let mut digits = 0;
+    while let Some(digit) = input.peek_char().and_then(|char| char.to_digit(10)) {
+        input.read_char();
+        digits += 1;
+        let digit = Value::from_u32(digit).unwrap();
+        number = number
+            .and_then(|number| CheckedMul::checked_mul(&number, &value(10)))
+            .and_then(|number| {
+                if negative {
+                    CheckedSub::checked_sub(&number, &digit)
+                } else {
+                    CheckedAdd::checked_add(&number, &digit)
+                }
+            });
+    }
+    if digits == 0 {
+        input.read_char();
+        return None;
     }
+    number
 }
 
 /// Reads a value from STDIN as either a number or character, depending on the particular incarnation of this command and pushes it on to the stack.
-/// If no input is waiting on STDIN, this is an error and the command is ignored.
-/// If an integer read does not receive an integer value, this is an error and the command is ignored.
-fn in_char(state: &mut State) {
-    if let Some(char) = state.stdin.pop() {
-        state.stack.push(char as isize)
-    }
+/// If no input is waiting on STDIN, what happens depends on the `EndOfInputPolicy`.
+fn in_char(state: &mut State) -> Outcome {
+    let char = match state.input.read_char() {
+        Some(char) => Value::from_u32(char as u32).unwrap(),
+        None => return end_of_input(state),
+    };
+    push_read(state, char)
+}
+
+/// Pushes a value that was read from input on to the stack.
+fn push_read(state: &mut State, read: Value) -> Outcome {
+    state.stack.push(read);
+    Ok(state.stack.last().cloned().map(Io::Read))
 }
 
 /// Pops the top value off the stack and prints it to STDOUT as either a number or character, depending on the particular incarnation of this command.
-fn out_number(state: &mut State) {
-    if let Some(top) = state.stack.pop() {
-        print!("{}", top)
-    }
+fn out_number(state: &mut State) -> Outcome {
+    let top = pop_one(state)?;
+    let text = top.to_string();
+    write(state, top, text)
 }
 
 /// Pops the top value off the stack and prints it to STDOUT as either a number or character, depending on the particular incarnation of this command.
-fn out_char(state: &mut State) {
-    if let Some(char) = state
-        .stack
-        .pop()
-        .and_then(|top| u32::try_from(top).ok())
-        .and_then(std::char::from_u32)
-    {
-        print!("{}", char)
+fn out_char(state: &mut State) -> Outcome {
+    let top = pop_one(state)?;
+    match top.to_u32().and_then(std::char::from_u32) {
+        Some(char) => write(state, top, char.to_string()),
+        None => {
+            state.stack.push(top);
+            Err(Skip::InvalidChar)
+        }
+    }
+}
+
+/// Writes the text for a popped value, putting the value back if the output can't be written to.
+fn write(state: &mut State, top: Value, text: String) -> Outcome {
+    match state.output.write_str(&text) {
+        Ok(()) => {
+            state.output_bytes += text.len();
+            Ok(Some(Io::Wrote(text)))
+        }
+        Err(_) => {
+            state.stack.push(top);
+            Err(Skip::OutputFailed)
+        }
     }
 }
 
 #[cfg(test)]
 mod test_command {
     use super::*;
+    use crate::stream::{BufferInput, BufferOutput, PietOutput};
+    use crate::value::values;
+    #[cfg(feature = "bigint")]
+    use parse::{chooser::Chooser, direction::Direction};
+
+    fn empty_state() -> State {
+        State::new(
+            Box::new(BufferInput::default()),
+            Box::new(BufferOutput::new()),
+        )
+    }
+
+    fn state_with_input(input: &str) -> State {
+        State::new(
+            Box::new(BufferInput::from(input)),
+            Box::new(BufferOutput::new()),
+        )
+    }
+
+    fn state_with_output() -> (State, BufferOutput) {
+        let output = BufferOutput::new();
+        let state = State::new(Box::new(BufferInput::default()), Box::new(output.clone()));
+        (state, output)
+    }
+
+    /// Output that always fails to write.
+    struct BrokenOutput;
+
+    impl PietOutput for BrokenOutput {
+        fn write_str(&mut self, _: &str) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_from_delta() {
+        assert_eq!(Command::from_delta(0, 0), None);
+        assert_eq!(Command::from_delta(6, 0), None);
+        assert_eq!(Command::from_delta(4, 1), Some(Command::Roll));
+        for command in Command::all() {
+            let (delta_hue, delta_lightness) = command.delta();
+            assert_eq!(
+                Command::from_delta(delta_hue, delta_lightness),
+                Some(command)
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Command::Subtract.to_string(), "subtract");
+        assert_eq!(Command::InNumber.to_string(), "in_number");
+        assert_eq!(Command::OutChar.to_string(), "out_char");
+    }
+
+    #[test]
+    fn test_next_for() {
+        let dark_blue = Color::Color {
+            hue: 4,
+            lightness: 2,
+        };
+        assert_eq!(
+            dark_blue.next_for(Command::OutNumber),
+            Some(Color::Color {
+                hue: 3,
+                lightness: 0
+            })
+        );
+        assert_eq!(Color::White.next_for(Command::Push), None);
+        for command in Command::all() {
+            let next = dark_blue.next_for(command).unwrap();
+            let (delta_hue, delta_lightness) = dark_blue.compare(&next).unwrap();
+            assert_eq!(
+                Command::from_delta(delta_hue, delta_lightness),
+                Some(command)
+            );
+        }
+    }
 
     #[test]
     fn test_push() {
-        let mut state = State::new(vec![]);
-        push(&mut state, 1);
-        assert_eq!(state.stack, vec![1]);
+        let mut state = empty_state();
+        assert_eq!(push(&mut state, 1), Ok(None));
+        assert_eq!(state.stack, values(&[1]));
     }
 
     #[test]
     fn test_pop() {
-        let mut state = State::new(vec![]);
-        state.stack.push(1);
-        pop(&mut state);
-        assert_eq!(state.stack, vec![]);
+        let mut state = empty_state();
+        state.stack.push(value(1));
+        assert_eq!(pop(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[]));
     }
 
     #[test]
     fn test_add() {
-        let mut state = State::new(vec![]);
-        state.stack.push(1);
-        state.stack.push(2);
-        add(&mut state);
-        assert_eq!(state.stack, vec![3]);
+        let mut state = empty_state();
+        state.stack.push(value(1));
+        state.stack.push(value(2));
+        assert_eq!(add(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[3]));
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        add(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(add(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
     }
 
     #[test]
     fn test_subtract() {
-        let mut state = State::new(vec![]);
-        state.stack.push(1);
-        state.stack.push(2);
-        subtract(&mut state);
-        assert_eq!(state.stack, vec![-1]);
+        let mut state = empty_state();
+        state.stack.push(value(1));
+        state.stack.push(value(2));
+        assert_eq!(subtract(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[-1]));
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        subtract(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(subtract(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
     }
 
     #[test]
     fn test_multiply() {
-        let mut state = State::new(vec![]);
-        state.stack.push(2);
-        state.stack.push(3);
-        multiply(&mut state);
-        assert_eq!(state.stack, vec![6]);
+        let mut state = empty_state();
+        state.stack.push(value(2));
+        state.stack.push(value(3));
+        assert_eq!(multiply(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[6]));
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        multiply(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(multiply(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
     }
 
     #[test]
     fn test_divide() {
-        let mut state = State::new(vec![]);
-        state.stack.push(7);
-        state.stack.push(3);
-        divide(&mut state);
-        assert_eq!(state.stack, vec![2]);
+        let mut state = empty_state();
+        state.stack.push(value(7));
+        state.stack.push(value(3));
+        assert_eq!(divide(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[2]));
 
-        let mut ignore_zero_state = State::new(vec![]);
-        ignore_zero_state.stack.push(7);
-        ignore_zero_state.stack.push(0);
-        divide(&mut ignore_zero_state);
-        assert_eq!(ignore_zero_state.stack, vec![7, 0]);
+        // (dividend, divisor, floored result, truncated result)
+        let cases = [
+            (7, 3, 2, 2),
+            (-7, 3, -3, -2),
+            (7, -3, -3, -2),
+            (-7, -3, 2, 2),
+            (-6, 3, -2, -2),
+        ];
+        for (dividend, divisor, floored, truncated) in cases {
+            for (division, result) in [
+                (DivisionRounding::Floor, floored),
+                (DivisionRounding::Truncate, truncated),
+            ] {
+                let mut state = empty_state();
+                state.dialect.division = division;
+                state.stack.push(value(dividend));
+                state.stack.push(value(divisor));
+                assert_eq!(divide(&mut state), Ok(None));
+                assert_eq!(state.stack, values(&[result]), "{} / {}", dividend, divisor);
+            }
+        }
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        divide(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut ignore_zero_state = empty_state();
+        ignore_zero_state.stack.push(value(7));
+        ignore_zero_state.stack.push(value(0));
+        assert_eq!(divide(&mut ignore_zero_state), Err(Skip::DivideByZero));
+        assert_eq!(ignore_zero_state.stack, values(&[7, 0]));
+
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(divide(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
     }
 
     #[test]
     fn test_modulo() {
-        let mut state = State::new(vec![]);
-        state.stack.push(7);
-        state.stack.push(3);
-        modulo(&mut state);
-        assert_eq!(state.stack, vec![1]);
+        let mut state = empty_state();
+        state.stack.push(value(7));
+        state.stack.push(value(3));
+        assert_eq!(modulo(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[1]));
+
+        // (dividend, divisor, result) where the result has the sign of the divisor
+        let cases = [
+            (7, 3, 1),
+            (-7, 3, 2),
+            (7, -3, -2),
+            (-7, -3, -1),
+            (-1, 3, 2),
+            (1, -3, -2),
+            (6, 3, 0),
+            (-6, 3, 0),
+            (6, -3, 0),
+            (-6, -3, 0),
+            (0, 3, 0),
+            (0, -3, 0),
+            (isize::MIN, -1, 0),
+            (isize::MIN, isize::MAX, isize::MAX - 1),
+        ];
+        for (dividend, divisor, result) in cases {
+            let mut state = empty_state();
+            state.stack.push(value(dividend));
+            state.stack.push(value(divisor));
+            assert_eq!(modulo(&mut state), Ok(None));
+            assert_eq!(
+                state.stack,
+                values(&[result]),
+                "{} mod {}",
+                dividend,
+                divisor
+            );
+        }
 
-        let mut ignore_zero_state = State::new(vec![]);
-        ignore_zero_state.stack.push(7);
-        ignore_zero_state.stack.push(0);
-        modulo(&mut ignore_zero_state);
-        assert_eq!(ignore_zero_state.stack, vec![7, 0]);
+        // (dividend, divisor, result) where the result has the sign of the dividend
+        let truncated = [
+            (7, 3, 1),
+            (-7, 3, -1),
+            (7, -3, 1),
+            (-7, -3, -1),
+            (-6, 3, 0),
+            (isize::MIN, -1, 0),
+        ];
+        for (dividend, divisor, result) in truncated {
+            let mut state = empty_state();
+            state.dialect.division = DivisionRounding::Truncate;
+            state.stack.push(value(dividend));
+            state.stack.push(value(divisor));
+            assert_eq!(modulo(&mut state), Ok(None));
+            assert_eq!(
+                state.stack,
+                values(&[result]),
+                "{} mod {}",
+                dividend,
+                divisor
+            );
+        }
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        modulo(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut ignore_zero_state = empty_state();
+        ignore_zero_state.stack.push(value(7));
+        ignore_zero_state.stack.push(value(0));
+        assert_eq!(modulo(&mut ignore_zero_state), Err(Skip::DivideByZero));
+        assert_eq!(ignore_zero_state.stack, values(&[7, 0]));
+
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(modulo(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
+    }
+
+    #[test]
+    #[cfg(not(feature = "bigint"))]
+    fn test_overflow() {
+        // (operation, second top, top, wrapped, saturated)
+        let cases = [
+            (Arithmetic::Add, isize::MAX, 1, isize::MIN, isize::MAX),
+            (Arithmetic::Subtract, isize::MIN, 1, isize::MAX, isize::MIN),
+            (Arithmetic::Multiply, isize::MAX, -2, 2, isize::MIN),
+            (
+                Arithmetic::Divide(DivisionRounding::Floor),
+                isize::MIN,
+                -1,
+                isize::MIN,
+                isize::MAX,
+            ),
+        ];
+        for (operation, two, one, wrapped, saturated) in cases {
+            for (overflow, expected) in [
+                (OverflowPolicy::Wrap, Ok(wrapped)),
+                (OverflowPolicy::Saturate, Ok(saturated)),
+                (OverflowPolicy::Ignore, Err(Skip::Overflow)),
+                (OverflowPolicy::Halt, Err(Skip::Overflow)),
+            ] {
+                let mut state = empty_state();
+                state.overflow = overflow;
+                state.stack.push(two);
+                state.stack.push(one);
+                let outcome = arithmetic(&mut state, operation);
+                match expected {
+                    Ok(result) => {
+                        assert_eq!(outcome, Ok(None));
+                        assert_eq!(state.stack, vec![result]);
+                    }
+                    Err(skip) => {
+                        assert_eq!(outcome, Err(skip));
+                        assert_eq!(state.stack, vec![two, one]);
+                    }
+                }
+                // Only `execute` knows the region needed to halt
+                assert_eq!(state.stopped, None);
+            }
+        }
     }
 
     #[test]
     fn test_not() {
-        let mut true_state = State::new(vec![]);
-        true_state.stack.push(0);
-        not(&mut true_state);
-        assert_eq!(true_state.stack, vec![1]);
+        let mut true_state = empty_state();
+        true_state.stack.push(value(0));
+        assert_eq!(not(&mut true_state), Ok(None));
+        assert_eq!(true_state.stack, values(&[1]));
 
-        let mut false_state = State::new(vec![]);
-        false_state.stack.push(33);
-        not(&mut false_state);
-        assert_eq!(false_state.stack, vec![0]);
+        let mut false_state = empty_state();
+        false_state.stack.push(value(33));
+        assert_eq!(not(&mut false_state), Ok(None));
+        assert_eq!(false_state.stack, values(&[0]));
     }
 
     #[test]
     fn test_greater() {
-        let mut greater_state = State::new(vec![]);
-        greater_state.stack.push(2);
-        greater_state.stack.push(1);
-        greater(&mut greater_state);
-        assert_eq!(greater_state.stack, vec![1]);
+        let mut greater_state = empty_state();
+        greater_state.stack.push(value(2));
+        greater_state.stack.push(value(1));
+        assert_eq!(greater(&mut greater_state), Ok(None));
+        assert_eq!(greater_state.stack, values(&[1]));
 
-        let mut lesser_state = State::new(vec![]);
-        lesser_state.stack.push(1);
-        lesser_state.stack.push(2);
-        greater(&mut lesser_state);
-        assert_eq!(lesser_state.stack, vec![0]);
+        let mut lesser_state = empty_state();
+        lesser_state.stack.push(value(1));
+        lesser_state.stack.push(value(2));
+        assert_eq!(greater(&mut lesser_state), Ok(None));
+        assert_eq!(lesser_state.stack, values(&[0]));
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        greater(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(greater(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
     }
 
     #[test]
     fn test_pointer() {
-        let mut state = State::new(vec![]);
-        let initial_direction = state.direction.clone();
-        state.stack.push(2);
-        pointer(&mut state);
+        let mut state = empty_state();
+        let initial_direction = state.direction;
+        state.stack.push(value(2));
+        assert_eq!(pointer(&mut state), Ok(None));
         assert_eq!(state.direction, initial_direction.next().next());
 
-        let mut wrapping_state = State::new(vec![]);
-        wrapping_state.stack.push(5);
-        pointer(&mut wrapping_state);
+        let mut wrapping_state = empty_state();
+        wrapping_state.stack.push(value(5));
+        assert_eq!(pointer(&mut wrapping_state), Ok(None));
         assert_eq!(wrapping_state.direction, initial_direction.next());
 
-        let mut negative_state = State::new(vec![]);
-        negative_state.stack.push(-3);
-        pointer(&mut negative_state);
+        let mut negative_state = empty_state();
+        negative_state.stack.push(value(-3));
+        assert_eq!(pointer(&mut negative_state), Ok(None));
         assert_eq!(negative_state.direction, initial_direction.next());
     }
 
     #[test]
     fn test_switch() {
-        let mut state = State::new(vec![]);
-        let initial_direction = state.chooser.clone();
-        state.stack.push(1);
-        switch(&mut state);
+        let mut state = empty_state();
+        let initial_direction = state.chooser;
+        state.stack.push(value(1));
+        assert_eq!(switch(&mut state), Ok(None));
         assert_eq!(state.chooser, initial_direction.next());
 
-        let mut wrapping_state = State::new(vec![]);
-        wrapping_state.stack.push(4);
-        switch(&mut wrapping_state);
+        let mut wrapping_state = empty_state();
+        wrapping_state.stack.push(value(4));
+        assert_eq!(switch(&mut wrapping_state), Ok(None));
         assert_eq!(wrapping_state.chooser, initial_direction);
 
-        let mut absolute_state = State::new(vec![]);
-        absolute_state.stack.push(-3);
-        switch(&mut absolute_state);
+        let mut absolute_state = empty_state();
+        absolute_state.stack.push(value(-3));
+        assert_eq!(switch(&mut absolute_state), Ok(None));
         assert_eq!(absolute_state.chooser, initial_direction.next());
     }
 
     #[test]
     fn test_duplicate() {
-        let mut state = State::new(vec![]);
-        state.stack.push(1);
-        duplicate(&mut state);
-        assert_eq!(state.stack, vec![1, 1]);
+        let mut state = empty_state();
+        state.stack.push(value(1));
+        assert_eq!(duplicate(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[1, 1]));
     }
 
     #[test]
     fn test_roll() {
-        let simple_stack: Vec<isize> = vec![1, 2, 3, 4, 5, 6];
+        let simple_stack = values(&[1, 2, 3, 4, 5, 6]);
 
-        let mut state = State::new(vec![]);
+        let mut state = empty_state();
         state.stack.append(&mut simple_stack.clone());
-        state.stack.push(3); // depth
-        state.stack.push(2); // turns
-        roll(&mut state);
-        assert_eq!(state.stack, vec![1, 2, 3, 5, 6, 4]);
+        state.stack.push(value(3)); // depth
+        state.stack.push(value(2)); // turns
+        assert_eq!(roll(&mut state), Ok(None));
+        assert_eq!(state.stack, values(&[1, 2, 3, 5, 6, 4]));
 
-        let mut negative_turns_state = State::new(vec![]);
+        let mut negative_turns_state = empty_state();
         negative_turns_state.stack.append(&mut simple_stack.clone());
-        negative_turns_state.stack.push(3); // depth
-        negative_turns_state.stack.push(-2); // turns
-        roll(&mut negative_turns_state);
-        assert_eq!(negative_turns_state.stack, vec![1, 2, 3, 6, 4, 5]);
+        negative_turns_state.stack.push(value(3)); // depth
+        negative_turns_state.stack.push(value(-2)); // turns
+        assert_eq!(roll(&mut negative_turns_state), Ok(None));
+        assert_eq!(negative_turns_state.stack, values(&[1, 2, 3, 6, 4, 5]));
 
-        let mut negative_depth_state = State::new(vec![]);
+        let mut negative_depth_state = empty_state();
         negative_depth_state.stack.append(&mut simple_stack.clone());
-        negative_depth_state.stack.push(-1); // depth
-        negative_depth_state.stack.push(2); // turns
+        negative_depth_state.stack.push(value(-1)); // depth
+        negative_depth_state.stack.push(value(2)); // turns
         let negative_depth_initial = negative_depth_state.stack.clone();
-        roll(&mut negative_depth_state);
+        assert_eq!(roll(&mut negative_depth_state), Err(Skip::InvalidRoll));
         assert_eq!(negative_depth_state.stack, negative_depth_initial);
 
-        let mut short_state = State::new(vec![]);
-        short_state.stack.push(1);
-        roll(&mut short_state);
-        assert_eq!(short_state.stack, vec![1]);
+        let mut discard_state = empty_state();
+        discard_state.dialect.negative_roll = NegativeRollPolicy::Discard;
+        discard_state.stack.append(&mut simple_stack.clone());
+        discard_state.stack.push(value(-1)); // depth
+        discard_state.stack.push(value(2)); // turns
+        assert_eq!(roll(&mut discard_state), Err(Skip::InvalidRoll));
+        assert_eq!(discard_state.stack, simple_stack);
+
+        let mut short_state = empty_state();
+        short_state.stack.push(value(1));
+        assert_eq!(roll(&mut short_state), Err(Skip::StackUnderflow));
+        assert_eq!(short_state.stack, values(&[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn test_huge_values() {
+        let huge = |digits: &str| digits.parse::<Value>().unwrap();
+        // 2^100 + 1 is one more than a multiple of four
+        let odd = huge("1267650600228229401496703205377");
+
+        let mut pointer_state = empty_state();
+        pointer_state.stack.push(odd.clone());
+        assert_eq!(pointer(&mut pointer_state), Ok(None));
+        assert_eq!(pointer_state.direction, Direction::Down);
+
+        let mut switch_state = empty_state();
+        switch_state.stack.push(odd.clone());
+        assert_eq!(switch(&mut switch_state), Ok(None));
+        assert_eq!(switch_state.chooser, Chooser::Right);
+
+        // 10^30 + 1 rolls is the same as 2 rolls at a depth of 3
+        let mut roll_state = empty_state();
+        roll_state.stack.append(&mut values(&[1, 2, 3, 3]));
+        roll_state
+            .stack
+            .push(huge("1000000000000000000000000000001"));
+        assert_eq!(roll(&mut roll_state), Ok(None));
+        assert_eq!(roll_state.stack, values(&[2, 3, 1]));
+
+        let (mut out_state, output) = state_with_output();
+        out_state.stack.push(odd.clone());
+        out_state.stack.push(odd.clone() * odd);
+        assert_eq!(out_char(&mut out_state), Err(Skip::InvalidChar));
+        assert_eq!(out_state.stack.len(), 2);
+        out_state.stack.pop();
+        assert!(out_number(&mut out_state).is_ok());
+        assert_eq!(output.contents(), "1267650600228229401496703205377");
     }
 
     #[test]
-    #[ignore]
     fn test_in_number() {
-        todo!();
+        let mut state = state_with_input("  42 -7\n+3\t0012\n");
+        for expected in [42, -7, 3, 12] {
+            assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(expected)))));
+        }
+        assert_eq!(in_number(&mut state), Err(Skip::NoInput));
+        assert_eq!(state.stack, values(&[42, -7, 3, 12]));
+    }
+
+    #[test]
+    fn test_in_number_leaves_trailing_input() {
+        let mut state = state_with_input("12ab");
+        assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(12)))));
+        assert_eq!(in_char(&mut state), Ok(Some(Io::Read(value('a' as isize)))));
+    }
+
+    #[test]
+    fn test_in_number_invalid() {
+        for input in ["x1", "-", "- 1"] {
+            let mut ignore_state = state_with_input(input);
+            assert_eq!(in_number(&mut ignore_state), Err(Skip::InvalidInput));
+            assert_eq!(ignore_state.stack, values(&[]));
+            assert_eq!(ignore_state.stopped, None);
+
+            let mut zero_state = state_with_input(input);
+            zero_state.dialect.invalid_number = InvalidNumberPolicy::Zero;
+            assert_eq!(in_number(&mut zero_state), Ok(Some(Io::Read(value(0)))));
+            assert_eq!(zero_state.stack, values(&[0]));
+
+            let mut halt_state = state_with_input(input);
+            halt_state.dialect.invalid_number = InvalidNumberPolicy::Halt;
+            assert_eq!(in_number(&mut halt_state), Err(Skip::InvalidInput));
+            assert_eq!(halt_state.stack, values(&[]));
+            assert_eq!(halt_state.stopped, Some(ExitReason::InvalidInput));
+        }
+
+        let mut state = state_with_input("x1");
+        assert_eq!(in_number(&mut state), Err(Skip::InvalidInput));
+        assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(1)))));
+    }
+
+    #[test]
+    fn test_in_number_consumes_invalid_input() {
+        // Reading in a loop moves past bad input rather than pushing a zero for it forever
+        let mut state = state_with_input("x");
+        state.dialect.invalid_number = InvalidNumberPolicy::Zero;
+        assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(0)))));
+        assert_eq!(in_number(&mut state), Err(Skip::NoInput));
+
+        // The sign and the character that isn't a digit are both consumed
+        let mut state = state_with_input("-x7");
+        assert_eq!(in_number(&mut state), Err(Skip::InvalidInput));
+        assert_eq!(in_char(&mut state), Ok(Some(Io::Read(value('7' as isize)))));
+
+        // A sign at the end of the input is invalid rather than the end of input
+        let mut state = state_with_input("-");
+        assert_eq!(in_number(&mut state), Err(Skip::InvalidInput));
+        assert_eq!(in_number(&mut state), Err(Skip::NoInput));
+    }
+
+    #[test]
+    fn test_in_number_large() {
+        let large = "99999999999999999999999";
+        let mut state = state_with_input(large);
+        if cfg!(feature = "bigint") {
+            assert!(in_number(&mut state).is_ok());
+            assert_eq!(state.stack[0].to_string(), large);
+        } else {
+            assert_eq!(in_number(&mut state), Err(Skip::InvalidInput));
+            assert_eq!(state.stack, values(&[]));
+        }
+    }
+
+    #[test]
+    fn test_in_number_digit() {
+        let mut state = state_with_input("42x");
+        state.dialect.number_input = NumberInput::Digit;
+        assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(4)))));
+        assert_eq!(in_number(&mut state), Ok(Some(Io::Read(value(2)))));
+        assert_eq!(in_number(&mut state), Err(Skip::InvalidInput));
+        assert_eq!(in_number(&mut state), Err(Skip::NoInput));
+        assert_eq!(state.stack, values(&[4, 2]));
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        let mut ignore_state = state_with_input("  ");
+        assert_eq!(in_number(&mut ignore_state), Err(Skip::NoInput));
+        assert_eq!(in_char(&mut ignore_state), Err(Skip::NoInput));
+        assert_eq!(ignore_state.stopped, None);
+
+        let mut push_state = empty_state();
+        push_state.dialect.end_of_input = EndOfInputPolicy::Push(-1);
+        assert_eq!(in_number(&mut push_state), Ok(Some(Io::Read(value(-1)))));
+        assert_eq!(in_char(&mut push_state), Ok(Some(Io::Read(value(-1)))));
+        assert_eq!(push_state.stack, values(&[-1, -1]));
+
+        let mut halt_state = empty_state();
+        halt_state.dialect.end_of_input = EndOfInputPolicy::Halt;
+        assert_eq!(in_char(&mut halt_state), Err(Skip::NoInput));
+        assert_eq!(halt_state.stopped, Some(ExitReason::InputExhausted));
     }
 
     #[test]
-    #[ignore]
     fn test_in_char() {
-        todo!();
+        let mut state = state_with_input("hé");
+        assert_eq!(in_char(&mut state), Ok(Some(Io::Read(value('h' as isize)))));
+        assert_eq!(in_char(&mut state), Ok(Some(Io::Read(value('é' as isize)))));
+        assert_eq!(in_char(&mut state), Err(Skip::NoInput));
+        assert_eq!(state.stack, values(&['h' as isize, 'é' as isize]));
     }
 
     #[test]
-    #[ignore]
     fn test_out_number() {
-        todo!();
+        let (mut state, output) = state_with_output();
+        state.stack.push(value(-12));
+        state.stack.push(value(3));
+        assert_eq!(out_number(&mut state), Ok(Some(Io::Wrote("3".to_string()))));
+        assert_eq!(
+            out_number(&mut state),
+            Ok(Some(Io::Wrote("-12".to_string())))
+        );
+        assert_eq!(out_number(&mut state), Err(Skip::StackUnderflow));
+        assert_eq!(output.contents(), "3-12");
+        assert_eq!(state.output_bytes, 4);
+
+        let mut broken_state = State::new(Box::new(BufferInput::default()), Box::new(BrokenOutput));
+        broken_state.stack.push(value(3));
+        assert_eq!(out_number(&mut broken_state), Err(Skip::OutputFailed));
+        assert_eq!(broken_state.stack, values(&[3]));
     }
 
     #[test]
-    #[ignore]
     fn test_out_char() {
-        todo!();
+        let (mut state, output) = state_with_output();
+        state.stack.push(value(-1));
+        state.stack.push(value('i' as isize));
+        state.stack.push(value('H' as isize));
+        assert_eq!(out_char(&mut state), Ok(Some(Io::Wrote("H".to_string()))));
+        assert_eq!(out_char(&mut state), Ok(Some(Io::Wrote("i".to_string()))));
+        assert_eq!(out_char(&mut state), Err(Skip::InvalidChar));
+        assert_eq!(state.stack, values(&[-1]));
+        assert_eq!(output.contents(), "Hi");
     }
 }
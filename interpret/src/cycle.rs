@@ -0,0 +1,137 @@
+use parse::region::RegionId;
+
+use crate::state::{Snapshot, State};
+
+/// Detects programs which are stuck repeating themselves.
+///
+/// A program whose state exactly matches an earlier one, without any I/O in between, will keep repeating the same
+/// steps forever since nothing else can influence it.
+/// Rather than remembering every state, this uses Brent's algorithm: each state is compared against a single
+/// checkpoint, which is moved to the current state whenever the number of steps since it was taken reaches a power of
+/// two. Loops are still found, just within a few times as many steps as it took to enter one and go around it once.
+///
+/// # Parameters
+///
+/// * `checkpoint` - The state that later ones are compared against.
+/// * `regions` - The region that the pointer was in at each step since the checkpoint was taken.
+/// * `window` - How many steps to compare against the checkpoint before moving it.
+#[derive(Debug)]
+pub(crate) struct LoopDetector {
+    checkpoint: Option<Snapshot>,
+    regions: Vec<RegionId>,
+    window: usize,
+}
+
+impl Default for LoopDetector {
+    fn default() -> Self {
+        Self {
+            checkpoint: None,
+            regions: Vec::new(),
+            window: 1,
+        }
+    }
+}
+
+impl LoopDetector {
+    /// Records the state at the start of a step, returning the steps since the checkpoint if it's a repeat of it.
+    pub(crate) fn record(&mut self, state: &State, region: RegionId) -> Option<&[RegionId]> {
+        match &self.checkpoint {
+            Some(checkpoint) if state.matches(checkpoint) => return Some(&self.regions),
+            Some(_) if self.regions.len() < self.window => (),
+            Some(_) => {
+                self.window *= 2;
+                self.checkpoint = Some(state.snapshot());
+                self.regions.clear();
+            }
+            None => self.checkpoint = Some(state.snapshot()),
+        }
+        self.regions.push(region);
+        None
+    }
+
+    /// Forgets everything recorded so far, since I/O could change what the program does next.
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod test_cycle {
+    use super::*;
+    use crate::stream::{BufferInput, BufferOutput};
+    use parse::color::Color;
+    use parse::direction::Direction;
+    use parse::program::Program;
+
+    fn state(pointer: (usize, usize), direction: Direction) -> State {
+        let mut state = State::new(
+            Box::new(BufferInput::default()),
+            Box::new(BufferOutput::new()),
+        );
+        state.pointer = pointer;
+        state.direction = direction;
+        state
+    }
+
+    #[test]
+    fn test_record() {
+        let colors = vec![vec![Color::White, Color::Black]];
+        let program = Program::new(colors, 1, 2);
+        let (white, black) = (program.region_id_at((0, 0)), program.region_id_at((0, 1)));
+
+        let mut detector = LoopDetector::default();
+        assert_eq!(
+            detector.record(&state((0, 0), Direction::Right), white),
+            None
+        );
+        assert_eq!(
+            detector.record(&state((0, 1), Direction::Right), black),
+            None
+        );
+        // The checkpoint moved to the previous state after one step, which this repeats
+        assert_eq!(
+            detector.record(&state((0, 1), Direction::Down), black),
+            None
+        );
+        assert_eq!(
+            detector.record(&state((0, 1), Direction::Right), black),
+            Some(&[black, black][..])
+        );
+
+        detector.clear();
+        assert_eq!(
+            detector.record(&state((0, 0), Direction::Right), white),
+            None
+        );
+        assert_eq!(
+            detector.record(&state((0, 0), Direction::Right), white),
+            Some(&[white][..])
+        );
+    }
+
+    #[test]
+    fn test_record_long_loop() {
+        // A loop of 5 steps is found once the checkpoint's window has grown to fit it
+        let colors = vec![vec![Color::White]];
+        let program = Program::new(colors, 1, 1);
+        let white = program.region_id_at((0, 0));
+        let directions = [
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+        ];
+
+        let mut detector = LoopDetector::default();
+        let mut found = None;
+        for step in 0..100 {
+            let mut state = state((0, 0), directions[step % 5 % 4]);
+            state.termination_counter = (step % 5) as u8;
+            if let Some(regions) = detector.record(&state, white) {
+                found = Some((step, regions.len()));
+                break;
+            }
+        }
+        assert_eq!(found, Some((12, 5)));
+    }
+}
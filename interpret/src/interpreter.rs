@@ -1,9 +1,18 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use parse::chooser::Chooser;
 use parse::color::Color;
+use parse::direction::Direction;
 use parse::program::Program;
 
-use crate::command::execute;
+use crate::command::{execute, Command};
+use crate::cycle::LoopDetector;
+use crate::options::{Dialect, OverflowPolicy, RunLimits, WhiteHandling};
 use crate::state::State;
-use parse::direction::Direction;
+use crate::step::{CommandResult, ExitReason, RunResult, StepResult};
+use crate::stream::{CountedInput, PietInput, PietOutput, ReadInput, WriteOutput};
+use crate::value::Value;
 
 /// An interpreter for a Piet program.
 ///
@@ -11,108 +20,697 @@ use parse::direction::Direction;
 ///
 /// * `program` - The program being run by the interpreter.
 /// * `state` - The state of the interpreter.
+/// * `limits` - Bounds on how much the program may do when it's run.
+/// * `steps` - The number of steps taken so far.
+/// * `loops` - Detects when the program is stuck in a loop, if enabled.
 pub struct Interpreter {
     program: Program,
     state: State,
+    limits: RunLimits,
+    steps: u64,
+    loops: Option<LoopDetector>,
 }
 
 impl Interpreter {
-    /// Initializes the interpreter with a program.
-    pub fn new(program: Program, stdin: Vec<char>) -> Self {
+    /// Initializes the interpreter with a program which reads from stdin and writes to stdout.
+    pub fn new(program: Program) -> Self {
         Interpreter {
             program,
-            state: State::new(stdin),
+            state: State::new(
+                Box::new(ReadInput::stdin()),
+                Box::new(WriteOutput::stdout()),
+            ),
+            limits: RunLimits::default(),
+            steps: 0,
+            loops: None,
+        }
+    }
+
+    /// Enables or disables stopping the program once it's stuck repeating the same steps forever.
+    pub fn with_loop_detection(mut self, enabled: bool) -> Self {
+        self.loops = if enabled {
+            Some(LoopDetector::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Replaces the bounds on how much the program may do when it's run.
+    pub fn with_limits(mut self, limits: RunLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Replaces how the spec's ambiguities are resolved.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.state.dialect = dialect;
+        self
+    }
+
+    /// Replaces what happens when arithmetic overflows.
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.state.overflow = overflow;
+        self
+    }
+
+    /// Replaces where the program reads its input from.
+    pub fn with_input<I: PietInput + 'static>(mut self, input: I) -> Self {
+        self.state.input = CountedInput::new(Box::new(input));
+        self
+    }
+
+    /// Replaces where the program writes its output to.
+    pub fn with_output<O: PietOutput + 'static>(mut self, output: O) -> Self {
+        self.state.output = Box::new(output);
+        self
+    }
+
+    /// Runs the interpreter until the program finishes or exceeds one of its limits.
+    pub fn run(&mut self) -> RunResult {
+        self.run_with(|_, _| ())
+    }
+
+    /// Runs the interpreter like `run`, calling `on_step` with the interpreter and the result of every step taken.
+    pub fn run_with<F: FnMut(&Interpreter, &StepResult)>(&mut self, mut on_step: F) -> RunResult {
+        let start = Instant::now();
+        let reason = loop {
+            if let Some(reason) = self.exit_reason().or_else(|| self.exceeded_limit(start)) {
+                break reason;
+            }
+            let result = self.step();
+            on_step(self, &result);
+        };
+        RunResult {
+            reason,
+            steps: self.steps,
         }
     }
 
-    /// Runs the interpreter until completion.
-    pub fn run(&mut self) {
-        while self.state.termination_counter < 8 {
-            self.advance();
+    /// Whether the program has finished.
+    pub fn is_halted(&self) -> bool {
+        self.exit_reason().is_some()
+    }
+
+    /// Why the program finished, or `None` if it hasn't.
+    pub fn exit_reason(&self) -> Option<ExitReason> {
+        if self.state.termination_counter >= 8 {
+            Some(ExitReason::Halted)
+        } else {
+            self.state.stopped.clone()
         }
     }
 
-    /// Advance the program state by one iteration.
-    fn advance(&mut self) {
-        if let Some((next_location, next_color, passed_white)) = self.next_coordinates() {
-            // If this is a region that we can move into, do it!
-            let (delta_hue, delta_lightness) = {
-                let current_color = &self.program.color_at(self.state.pointer);
-                // If we passed through or are in a white region then we never execute a command
-                if current_color == &&Color::White || passed_white {
-                    (0, 0)
-                } else {
-                    current_color.compare(&next_color).unwrap()
-                }
-            };
-            let current_region_size = self.program.region_at(self.state.pointer).size;
-            execute(
-                &mut self.state,
-                delta_hue,
-                delta_lightness,
-                current_region_size,
-            );
-            self.state.pointer = next_location;
-            // Reset the termination counter if we've found a path forwards
-            self.state.termination_counter = 0;
+    /// The first limit that the program has gone beyond, if any.
+    fn exceeded_limit(&self, start: Instant) -> Option<ExitReason> {
+        let limits = &self.limits;
+        let over = |limit: Option<usize>, value: usize| limit.is_some_and(|limit| value > limit);
+        if limits.max_steps.is_some_and(|max| self.steps >= max) {
+            Some(ExitReason::StepLimit)
+        } else if over(limits.max_stack_depth, self.state.stack.len()) {
+            Some(ExitReason::StackLimit)
+        } else if over(limits.max_output_bytes, self.state.output_bytes) {
+            Some(ExitReason::OutputLimit)
+        } else if limits
+            .wall_clock_timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            Some(ExitReason::Timeout)
         } else {
-            self.state.collide();
+            None
         }
     }
 
-    /// Returns the next coordinates of the display pointer
-    ///
-    /// # Returns
-    /// If the next region exists:
-    ///  - coordinates of the next region
-    ///  - the next region's color
-    ///  - whether a white region was traversed
-    fn next_coordinates(&self) -> Option<((usize, usize), &Color, bool)> {
-        let direction = self.state.direction;
-        let first_edge = self.next_disjoint_edge(self.state.pointer, direction);
-        let second_edge = self.next_disjoint_edge(first_edge, self.state.chooser.choose(direction));
-
-        // Check if we're moving into:
-        //  - either the edge of the program or a black codel in which case we stop
-        //  - a white codel in which case we:
-        //    - find and move to the first edge and then stop (even if there are other non-contiguous ones later)
-        //    - step into it if it's a colored codel, otherwise stay in the current white codel
-        //  - a colored codel in which case we step one square into it and stop
-        self.program
-            .next_point(second_edge, direction)
-            .and_then(|(next_location, next_color)| {
-                match next_color {
-                    Color::Black => None,
-                    Color::White => {
-                        // Find the first edge of the white region ignoring any potential further, disjoint ones
-                        let white_edge = self.next_edge(next_location, direction);
-                        // If we're about to step into a Color::Color codel, do it; otherwise, stop at the edge
-                        match self.program.next_point(white_edge, direction) {
-                            Some((point, color @ Color::Color { .. })) => {
-                                Some((point, color, true))
-                            }
-                            _ => Some((white_edge, &Color::White, true)),
+    /// The number of steps taken so far, not counting any after the program halted.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The program being run.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// The (row, column) coordinates of the codel that the pointer is in.
+    pub fn pointer(&self) -> (usize, usize) {
+        self.state.pointer
+    }
+
+    /// The current direction pointer.
+    pub fn direction(&self) -> Direction {
+        self.state.direction
+    }
+
+    /// The current codel chooser.
+    pub fn chooser(&self) -> Chooser {
+        self.state.chooser
+    }
+
+    /// The current stack, where the last element is the top.
+    pub fn stack(&self) -> &[Value] {
+        &self.state.stack
+    }
+
+    /// Advance the program state by one iteration, either moving into the next region or changing the DP/CC after being blocked.
+    /// Once the program has halted this does nothing.
+    pub fn step(&mut self) -> StepResult {
+        let left = self.program.region_id_at(self.state.pointer);
+        let (direction, chooser) = (self.state.direction, self.state.chooser);
+        let chars_read = self.state.input.chars_read;
+        let mut result = StepResult {
+            left,
+            entered: None,
+            command: None,
+            direction: (direction, direction),
+            chooser: (chooser, chooser),
+            io: None,
+            halted: self.is_halted(),
+        };
+        if result.halted {
+            return result;
+        }
+        if let Some(loops) = &mut self.loops {
+            if let Some(regions) = loops.record(&self.state, left) {
+                self.state.stopped = Some(ExitReason::Loop {
+                    regions: regions.to_vec(),
+                });
+                result.halted = true;
+                return result;
+            }
+        }
+        self.steps += 1;
+
+        let current_color = self.program.color_at(self.state.pointer).clone();
+        let white = self.state.dialect.white;
+        // Moving out of a white codel is the same as sliding through a white region that we've just entered
+        let next = if current_color == Color::White && white == WhiteHandling::Slide {
+            Some((self.state.pointer, Color::White))
+        } else {
+            self.next_coordinates()
+        };
+
+        match next {
+            None => self.state.collide(),
+            // We never execute a command when passing through a white region
+            Some((start, Color::White)) if white == WhiteHandling::Legacy => {
+                let next_location = self.legacy_slide(start);
+                self.move_to(next_location, &mut result);
+            }
+            Some((start, Color::White)) => match self.slide(start) {
+                Slide::Entered(next_location) => self.move_to(next_location, &mut result),
+                Slide::Trapped(last) => {
+                    self.state.pointer = last;
+                    let region = self.program.region_id_at(last);
+                    result.entered = if region != left { Some(region) } else { None };
+                    self.state.stopped = Some(ExitReason::Halted);
+                }
+            },
+            Some((next_location, next_color)) => {
+                // Leaving a white region (which only happens with legacy white handling) never executes a command
+                let command =
+                    current_color
+                        .compare(&next_color)
+                        .and_then(|(delta_hue, delta_lightness)| {
+                            Command::from_delta(delta_hue, delta_lightness)
+                        });
+                let current_region_size = self.program.region_at(self.state.pointer).size;
+                if let Some(command) = command {
+                    match execute(&mut self.state, command, left, current_region_size) {
+                        Ok(io) => {
+                            result.command = Some(CommandResult::Executed(command));
+                            result.io = io;
                         }
+                        Err(skip) => result.command = Some(CommandResult::Skipped(command, skip)),
                     }
-                    color @ Color::Color { .. } => Some((next_location, color, false)),
                 }
-            })
+                // A command that stops the program leaves the pointer where it was
+                if self.state.stopped.is_none() {
+                    self.move_to(next_location, &mut result);
+                }
+            }
+        }
+
+        result.direction.1 = self.state.direction;
+        result.chooser.1 = self.state.chooser;
+        result.halted = self.is_halted();
+        // Input isn't part of the snapshot, and can be consumed without any I/O being reported
+        let input_changed = self.state.input.chars_read != chars_read;
+        if let Some(loops) = &mut self.loops {
+            if result.io.is_some() || input_changed {
+                loops.clear();
+            }
+        }
+        result
+    }
+
+    /// Moves the pointer into a new region after finding a path forwards.
+    fn move_to(&mut self, next_location: (usize, usize), result: &mut StepResult) {
+        self.state.pointer = next_location;
+        result.entered = Some(self.program.region_id_at(next_location));
+        // Reset the termination counter if we've found a path forwards
+        self.state.termination_counter = 0;
+    }
+
+    /// Returns the coordinates and color of the codel that the pointer would move into from its current region,
+    /// or `None` if it's blocked by the edge of the program or a black codel.
+    fn next_coordinates(&self) -> Option<((usize, usize), Color)> {
+        self.program
+            .region_at(self.state.pointer)
+            .exit(self.state.direction, self.state.chooser)
+            .next
+            .clone()
+            .filter(|(_, next_color)| next_color != &Color::Black)
+    }
+
+    /// Slides through a white region in a straight line starting from the white codel at `start`.
+    ///
+    /// Whenever the slide is blocked, the codel chooser is toggled and the direction pointer is rotated clockwise before
+    /// sliding on in the new direction. These changes persist after leaving the white region.
+    /// If the same white codel is ever revisited with the same direction pointer then the path is repeating forever.
+    fn slide(&mut self, start: (usize, usize)) -> Slide {
+        let mut visited = HashSet::new();
+        let mut pointer = start;
+        loop {
+            if !visited.insert((pointer, self.state.direction)) {
+                break Slide::Trapped(pointer);
+            }
+            match self.program.next_point(pointer, self.state.direction) {
+                Some((next, Color::White)) => pointer = next,
+                Some((next, Color::Color { .. })) => break Slide::Entered(next),
+                _ => {
+                    self.state.chooser = self.state.chooser.next();
+                    self.state.direction = self.state.direction.next();
+                }
+            }
+        }
     }
 
-    /// The coordinate of the closest region edge (exclusive) reached starting from `start` and moving in `direction`.
-    fn next_edge(&self, start: (usize, usize), direction: Direction) -> (usize, usize) {
-        let color = self.program.color_at(start);
+    /// Moves straight through a white region starting from the white codel at `start`, stopping at its far edge
+    /// (ignoring any further, disjoint white codels), and then steps into the next codel if it's colored.
+    fn legacy_slide(&self, start: (usize, usize)) -> (usize, usize) {
+        let direction = self.state.direction;
         let mut pointer = start;
         loop {
             match self.program.next_point(pointer, direction) {
-                Some((next_pointer, next_color)) if next_color == color => pointer = next_pointer,
+                Some((next, Color::White)) => pointer = next,
+                Some((next, Color::Color { .. })) => break next,
                 _ => break pointer,
             }
         }
     }
+}
+
+/// Where sliding through a white region ended up.
+enum Slide {
+    /// The slide entered the colored codel at these coordinates.
+    Entered((usize, usize)),
+    /// The slide started repeating itself, last reaching the white codel at these coordinates.
+    Trapped((usize, usize)),
+}
+
+#[cfg(test)]
+mod test_interpreter {
+    use super::*;
+    use crate::command::{Io, Skip};
+    use crate::options::{EndOfInputPolicy, InvalidNumberPolicy};
+    use crate::stream::{BufferInput, BufferOutput};
+    use crate::value::{value, values};
+    use std::time::Duration;
+
+    const A: Color = Color::Color {
+        hue: 0,
+        lightness: 0,
+    };
+    const B: Color = Color::Color {
+        hue: 1,
+        lightness: 0,
+    };
+    const C: Color = Color::Color {
+        hue: 2,
+        lightness: 0,
+    };
+
+    fn buffered(colors: Vec<Vec<Color>>, input: &str) -> Interpreter {
+        let (rows, cols) = (colors.len(), colors[0].len());
+        Interpreter::new(Program::new(colors, rows, cols))
+            .with_input(BufferInput::from(input))
+            .with_output(BufferOutput::new())
+    }
+
+    #[test]
+    fn test_next_coordinates_l_shape() {
+        let colors = vec![vec![A, B, B, B], vec![A, B, B, B], vec![A, A, A, C]];
+        let interpreter = buffered(colors, "");
+        assert_eq!(interpreter.next_coordinates(), Some(((2, 3), C)));
+    }
+
+    #[test]
+    fn test_next_coordinates_ring() {
+        let colors = vec![
+            vec![A, A, A, A, C],
+            vec![A, C, A, B, B],
+            vec![A, A, A, B, B],
+        ];
+        let mut interpreter = buffered(colors, "");
+        interpreter.state.pointer = (2, 0);
+        assert_eq!(interpreter.next_coordinates(), Some(((0, 4), C)));
+
+        interpreter.state.direction = Direction::Down;
+        assert_eq!(interpreter.next_coordinates(), None);
+    }
+
+    #[test]
+    fn test_slide_straight() {
+        const W: Color = Color::White;
+        let colors = vec![vec![A, W, W, B, Color::Black]];
+        let mut interpreter = buffered(colors, "");
+        let step = interpreter.step();
+        assert_eq!(step.command, None);
+        assert_eq!(
+            step.entered,
+            Some(interpreter.program().region_id_at((0, 3)))
+        );
+        assert_eq!(step.direction, (Direction::Right, Direction::Right));
+        assert_eq!(interpreter.pointer(), (0, 3));
+    }
+
+    #[test]
+    fn test_slide_around_obstructions() {
+        const W: Color = Color::White;
+        const K: Color = Color::Black;
+        // Slide right into black, turn down and slide into the edge, then turn left into B
+        let colors = vec![vec![A, W, K], vec![K, W, W], vec![B, W, W]];
+        let mut interpreter = buffered(colors, "");
+        let step = interpreter.step();
+        assert_eq!(step.command, None);
+        assert_eq!(interpreter.pointer(), (2, 0));
+        assert_eq!(step.direction, (Direction::Right, Direction::Left));
+        assert_eq!(step.chooser, (Chooser::Left, Chooser::Left));
+        assert!(!step.halted);
+    }
+
+    #[test]
+    fn test_slide_trapped() {
+        const W: Color = Color::White;
+        const K: Color = Color::Black;
+        // Starting in white with every direction blocked, the slide repeats itself
+        let colors = vec![vec![W, K], vec![K, K]];
+        let mut interpreter = buffered(colors, "");
+        let step = interpreter.step();
+        assert_eq!(step.entered, None);
+        assert!(step.halted);
+        assert_eq!(interpreter.pointer(), (0, 0));
+
+        // A loop around a white ring is also detected
+        let colors = vec![vec![A, W, W, W], vec![K, W, K, W], vec![K, W, W, W]];
+        let mut interpreter = buffered(colors, "");
+        assert!(interpreter.step().halted);
+    }
+
+    #[test]
+    fn test_legacy_white() {
+        const W: Color = Color::White;
+        const K: Color = Color::Black;
+        // Legacy handling stops at the edge of the white region rather than turning
+        let colors = vec![vec![A, W, K], vec![K, W, W], vec![B, W, W]];
+        let mut interpreter = buffered(colors, "").with_dialect(Dialect::legacy());
+        let step = interpreter.step();
+        assert_eq!(step.command, None);
+        assert_eq!(step.direction, (Direction::Right, Direction::Right));
+        assert_eq!(interpreter.pointer(), (0, 1));
+
+        // It then leaves the white region through its exit like any other region, without executing a command
+        interpreter.state.direction = Direction::Left;
+        let step = interpreter.step();
+        assert_eq!(step.command, None);
+        assert_eq!(interpreter.pointer(), (2, 0));
+    }
+
+    #[test]
+    fn test_step() {
+        // light red (1) -> red (2) pushes 1, red (2) -> light red (1) pops it, then light red is blocked
+        let red = Color::Color {
+            hue: 0,
+            lightness: 1,
+        };
+        let colors = vec![vec![A, red.clone(), red, A, Color::Black]];
+        let program = Program::new(colors, 1, 5);
+        let (first, second, third) = (
+            program.region_id_at((0, 0)),
+            program.region_id_at((0, 1)),
+            program.region_id_at((0, 3)),
+        );
+        let mut interpreter = Interpreter::new(program).with_input(BufferInput::default());
+
+        let push = interpreter.step();
+        assert_eq!(push.left, first);
+        assert_eq!(push.entered, Some(second));
+        assert_eq!(push.command, Some(CommandResult::Executed(Command::Push)));
+        assert_eq!(push.direction, (Direction::Right, Direction::Right));
+        assert!(!push.halted);
+        assert_eq!(interpreter.stack(), values(&[1]));
+
+        let pop = interpreter.step();
+        assert_eq!((pop.left, pop.entered), (second, Some(third)));
+        assert_eq!(pop.command, Some(CommandResult::Executed(Command::Pop)));
+        assert_eq!(interpreter.stack(), values(&[]));
 
-    /// The coordinate of the farthest region edge (exclusive) reached starting from `start` and moving in `direction`.
-    fn next_disjoint_edge(&self, start: (usize, usize), direction: Direction) -> (usize, usize) {
-        self.program.region_at(start).edge(start, direction)
+        let blocked = interpreter.step();
+        assert_eq!((blocked.left, blocked.entered), (third, None));
+        assert_eq!(blocked.command, None);
+        assert_eq!(blocked.chooser, (Chooser::Left, Chooser::Right));
+    }
+
+    #[test]
+    fn test_step_skipped_and_io() {
+        // A (light red) -> dark magenta is out_char (5, 2) which underflows
+        let dark_magenta = Color::Color {
+            hue: 5,
+            lightness: 2,
+        };
+        let colors = vec![vec![A, dark_magenta, Color::Black]];
+        let mut interpreter = buffered(colors, "");
+        assert_eq!(
+            interpreter.step().command,
+            Some(CommandResult::Skipped(
+                Command::OutChar,
+                Skip::StackUnderflow
+            ))
+        );
+
+        // light red -> light magenta is in_char (5, 0)
+        let light_magenta = Color::Color {
+            hue: 5,
+            lightness: 0,
+        };
+        let colors = vec![vec![A, light_magenta, Color::Black]];
+        let mut interpreter = buffered(colors, "x");
+        let step = interpreter.step();
+        assert_eq!(step.command, Some(CommandResult::Executed(Command::InChar)));
+        assert_eq!(step.io, Some(Io::Read(value('x' as isize))));
+    }
+
+    #[test]
+    fn test_legacy_matches_old_interpreter() {
+        // Reads 2 and 9 as single digits, prints (2 - 9) / 2 and (2 - 9) mod 2, passes through white, then prints a newline.
+        // The interpreter from before dialects were added prints "-3-1\n" for this input and then loops forever.
+        let program = Program::from_text("aqipnlhmtaqaldbcn\nssssssssssssssssb\n").unwrap();
+        let output = BufferOutput::new();
+        let mut interpreter = Interpreter::new(program)
+            .with_dialect(Dialect::legacy())
+            .with_input(BufferInput::from("29225\n"))
+            .with_output(output.clone())
+            .with_limits(RunLimits {
+                max_steps: Some(1000),
+                ..RunLimits::default()
+            });
+        assert_eq!(interpreter.run().reason, ExitReason::StepLimit);
+        assert_eq!(output.contents(), "-3-1\n");
+    }
+
+    #[test]
+    fn test_invalid_number_halts() {
+        // light red -> dark blue is in_number (4, 2)
+        let dark_blue = Color::Color {
+            hue: 4,
+            lightness: 2,
+        };
+        let colors = vec![vec![A, dark_blue, Color::Black]];
+        let mut interpreter = buffered(colors, "nope").with_dialect(Dialect {
+            invalid_number: InvalidNumberPolicy::Halt,
+            ..Dialect::spec()
+        });
+        let step = interpreter.step();
+        assert_eq!(
+            step.command,
+            Some(CommandResult::Skipped(
+                Command::InNumber,
+                Skip::InvalidInput
+            ))
+        );
+        assert_eq!(step.entered, None);
+        assert!(step.halted);
+        assert_eq!(interpreter.pointer(), (0, 0));
+    }
+
+    #[test]
+    fn test_run_halts() {
+        let colors = vec![vec![A, Color::Black]];
+        let mut interpreter = buffered(colors, "");
+        assert_eq!(
+            interpreter.run(),
+            RunResult {
+                reason: ExitReason::Halted,
+                steps: 8
+            }
+        );
+        assert!(interpreter.is_halted());
+        assert!(interpreter.step().halted);
+        assert_eq!(interpreter.steps(), 8);
+    }
+
+    #[test]
+    fn test_run_limits() {
+        // Bouncing between two regions never halts
+        let looping = || buffered(vec![vec![A, B]], "");
+        let limits = RunLimits {
+            max_steps: Some(10),
+            ..RunLimits::default()
+        };
+        assert_eq!(
+            looping().with_limits(limits).run(),
+            RunResult {
+                reason: ExitReason::StepLimit,
+                steps: 10
+            }
+        );
+
+        let limits = RunLimits {
+            wall_clock_timeout: Some(Duration::from_millis(10)),
+            ..RunLimits::default()
+        };
+        assert_eq!(
+            looping().with_limits(limits).run().reason,
+            ExitReason::Timeout
+        );
+
+        // light red -> red pushes
+        let red = Color::Color {
+            hue: 0,
+            lightness: 1,
+        };
+        let limits = RunLimits {
+            max_stack_depth: Some(0),
+            ..RunLimits::default()
+        };
+        let mut interpreter = buffered(vec![vec![A, red, Color::Black]], "").with_limits(limits);
+        assert_eq!(
+            interpreter.run(),
+            RunResult {
+                reason: ExitReason::StackLimit,
+                steps: 1
+            }
+        );
+
+        // light red -> magenta is out_number (5, 1)
+        let magenta = Color::Color {
+            hue: 5,
+            lightness: 1,
+        };
+        let limits = RunLimits {
+            max_output_bytes: Some(2),
+            ..RunLimits::default()
+        };
+        let mut interpreter =
+            buffered(vec![vec![A, magenta, Color::Black]], "").with_limits(limits);
+        interpreter.state.stack.push(value(123));
+        assert_eq!(interpreter.run().reason, ExitReason::OutputLimit);
+    }
+
+    #[test]
+    #[cfg(not(feature = "bigint"))]
+    fn test_run_overflow_halts() {
+        // light red -> light yellow is add (1, 0)
+        let colors = vec![vec![A, B, Color::Black]];
+        let mut interpreter = buffered(colors, "").with_overflow(OverflowPolicy::Halt);
+        interpreter.state.stack = vec![isize::MAX, 1];
+        let region = interpreter.program().region_id_at((0, 0));
+        assert_eq!(
+            interpreter.run(),
+            RunResult {
+                reason: ExitReason::Overflow {
+                    region,
+                    command: Command::Add
+                },
+                steps: 1
+            }
+        );
+        assert_eq!(interpreter.stack(), &[isize::MAX, 1]);
+        assert_eq!(interpreter.pointer(), (0, 0));
+    }
+
+    #[test]
+    fn test_run_detects_loops() {
+        // Bouncing between two regions without I/O repeats forever
+        let mut interpreter = buffered(vec![vec![A, B]], "").with_loop_detection(true);
+        let (a, b) = (
+            interpreter.program().region_id_at((0, 0)),
+            interpreter.program().region_id_at((0, 1)),
+        );
+        let result = interpreter.run();
+        match result.reason {
+            ExitReason::Loop { regions } => {
+                assert!(regions.contains(&a) && regions.contains(&b));
+                assert!(regions.len() as u64 <= result.steps);
+            }
+            reason => panic!("expected a loop, got {:?}", reason),
+        }
+
+        // Reading input resets detection, so a loop that sums its input only stops once the input runs out
+        // light red -> light magenta is in_char (5, 0), and light magenta -> light red is add (1, 0)
+        let light_magenta = Color::Color {
+            hue: 5,
+            lightness: 0,
+        };
+        let mut interpreter =
+            buffered(vec![vec![A, light_magenta]], "abc").with_loop_detection(true);
+        assert!(matches!(interpreter.run().reason, ExitReason::Loop { .. }));
+        assert_eq!(interpreter.stack(), values(&[97 + 98 + 99]));
+    }
+
+    #[test]
+    fn test_run_invalid_input_is_not_a_loop() {
+        // light red -> dark blue is in_number (4, 2), which consumes each `x` without reporting any I/O.
+        // Only once the 5 has been read and the input has run out does the program repeat itself.
+        for input in ["xx5", "xxxxxxxxxxxxxxxx5"] {
+            let program = Program::from_text("aq\n").unwrap();
+            let mut interpreter = Interpreter::new(program)
+                .with_input(BufferInput::from(input))
+                .with_output(BufferOutput::new())
+                .with_loop_detection(true);
+            interpreter.run();
+            assert_eq!(interpreter.stack(), values(&[5]), "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_run_input_exhausted() {
+        // light red -> light magenta is in_char (5, 0)
+        let light_magenta = Color::Color {
+            hue: 5,
+            lightness: 0,
+        };
+        let colors = vec![vec![A, light_magenta, Color::Black]];
+        let mut interpreter = buffered(colors, "").with_dialect(Dialect {
+            end_of_input: EndOfInputPolicy::Halt,
+            ..Dialect::spec()
+        });
+        assert_eq!(
+            interpreter.run(),
+            RunResult {
+                reason: ExitReason::InputExhausted,
+                steps: 1
+            }
+        );
     }
 }
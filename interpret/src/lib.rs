@@ -1,4 +1,9 @@
-mod chooser;
-mod command;
+pub mod command;
+mod cycle;
 pub mod interpreter;
+pub mod options;
 mod state;
+pub mod step;
+pub mod stream;
+pub mod trace;
+pub mod value;
@@ -0,0 +1,239 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the pointer moves through white regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhiteHandling {
+    /// Slide through white in a straight line, turning on obstructions and halting if the path repeats.
+    Slide,
+    /// Stop at the far edge of the white region, stepping into a colored codel if there's one straight ahead.
+    Legacy,
+}
+
+/// What `roll` does when the depth is negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeRollPolicy {
+    /// Ignore the command, leaving the stack untouched.
+    Ignore,
+    /// Pop the depth and number of rolls but don't roll anything.
+    Discard,
+}
+
+/// How `divide` rounds results that aren't whole numbers, and so which sign `modulo` gives its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivisionRounding {
+    /// Round towards negative infinity, so remainders have the sign of the divisor as the spec requires.
+    Floor,
+    /// Round towards zero like C's `/`, so remainders have the sign of the dividend like C's `%`.
+    Truncate,
+}
+
+/// How `in_number` reads a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberInput {
+    /// Skip whitespace and read an optional sign followed by a run of digits.
+    Integer,
+    /// Read a single character as a digit.
+    Digit,
+}
+
+/// What `in_number` does when the input isn't a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidNumberPolicy {
+    /// Ignore the command, leaving the input where it is.
+    Ignore,
+    /// Push 0 in place of the number.
+    Zero,
+    /// Stop the program.
+    Halt,
+}
+
+impl FromStr for InvalidNumberPolicy {
+    type Err = String;
+
+    /// Parses one of `ignore`, `zero`, or `halt`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(InvalidNumberPolicy::Ignore),
+            "zero" => Ok(InvalidNumberPolicy::Zero),
+            "halt" => Ok(InvalidNumberPolicy::Halt),
+            _ => Err(format!(
+                "expected one of `ignore`, `zero` or `halt`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// What `in_char` and `in_number` do when there's no input left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndOfInputPolicy {
+    /// Ignore the command.
+    Ignore,
+    /// Push this value in place of the input.
+    Push(isize),
+    /// Stop the program.
+    Halt,
+}
+
+/// What happens when arithmetic overflows the range of a stack value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wrap around at the boundary of the range.
+    Wrap,
+    /// Clamp the result to the boundary of the range.
+    Saturate,
+    /// Ignore the command, leaving the stack untouched.
+    Ignore,
+    /// Stop the program.
+    Halt,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    /// Parses one of `wrap`, `saturate`, `ignore`, or `halt`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(OverflowPolicy::Wrap),
+            "saturate" => Ok(OverflowPolicy::Saturate),
+            "ignore" => Ok(OverflowPolicy::Ignore),
+            "halt" => Ok(OverflowPolicy::Halt),
+            _ => Err(format!(
+                "expected one of `wrap`, `saturate`, `ignore` or `halt`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// The choices that an interpreter makes where the spec is ambiguous or where other implementations disagree with it.
+///
+/// # Parameters
+///
+/// * `white` - How the pointer moves through white regions.
+/// * `negative_roll` - What `roll` does when the depth is negative.
+/// * `division` - How `divide` rounds results that aren't whole numbers.
+/// * `number_input` - How `in_number` reads a number.
+/// * `invalid_number` - What `in_number` does when the input isn't a number.
+/// * `end_of_input` - What `in_char` and `in_number` do when there's no input left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub white: WhiteHandling,
+    pub negative_roll: NegativeRollPolicy,
+    pub division: DivisionRounding,
+    pub number_input: NumberInput,
+    pub invalid_number: InvalidNumberPolicy,
+    pub end_of_input: EndOfInputPolicy,
+}
+
+impl Dialect {
+    /// Follows the spec as closely as possible, ignoring commands wherever it recommends doing so.
+    pub fn spec() -> Self {
+        Dialect {
+            white: WhiteHandling::Slide,
+            negative_roll: NegativeRollPolicy::Ignore,
+            division: DivisionRounding::Floor,
+            number_input: NumberInput::Integer,
+            invalid_number: InvalidNumberPolicy::Ignore,
+            end_of_input: EndOfInputPolicy::Ignore,
+        }
+    }
+
+    /// Models two of npiet's behaviours on top of `spec()`, which is used for everything else:
+    ///  - `divide` and `modulo` round towards zero, the way that npiet's C arithmetic does
+    ///  - `in_number` and `in_char` push -1 once the input has run out
+    pub fn npiet() -> Self {
+        Dialect {
+            division: DivisionRounding::Truncate,
+            end_of_input: EndOfInputPolicy::Push(-1),
+            ..Self::spec()
+        }
+    }
+
+    /// Matches how this interpreter behaved before dialects were added:
+    ///  - the pointer moves straight through white regions rather than sliding
+    ///  - `divide` and `modulo` round towards zero
+    ///  - `in_number` reads a single digit
+    ///  - invalid input, the end of input and negative rolls are all ignored
+    pub fn legacy() -> Self {
+        Dialect {
+            white: WhiteHandling::Legacy,
+            negative_roll: NegativeRollPolicy::Ignore,
+            division: DivisionRounding::Truncate,
+            number_input: NumberInput::Digit,
+            invalid_number: InvalidNumberPolicy::Ignore,
+            end_of_input: EndOfInputPolicy::Ignore,
+        }
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self::spec()
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    /// Parses one of `spec`, `npiet`, or `legacy`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spec" => Ok(Dialect::spec()),
+            "npiet" => Ok(Dialect::npiet()),
+            "legacy" => Ok(Dialect::legacy()),
+            _ => Err(format!(
+                "expected one of `spec`, `npiet` or `legacy`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// Bounds on how much a program may do before it's stopped, where `None` means unlimited.
+///
+/// # Parameters
+///
+/// * `max_steps` - The number of steps that the program may take.
+/// * `max_stack_depth` - The number of values that the stack may hold.
+/// * `max_output_bytes` - The number of bytes of output that the program may write.
+/// * `wall_clock_timeout` - How long the program may run for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    pub max_steps: Option<u64>,
+    pub max_stack_depth: Option<usize>,
+    pub max_output_bytes: Option<usize>,
+    pub wall_clock_timeout: Option<Duration>,
+}
+
+#[cfg(test)]
+mod test_options {
+    use super::*;
+
+    #[test]
+    fn test_parse_invalid_number_policy() {
+        assert_eq!("ignore".parse(), Ok(InvalidNumberPolicy::Ignore));
+        assert_eq!("zero".parse(), Ok(InvalidNumberPolicy::Zero));
+        assert_eq!("halt".parse(), Ok(InvalidNumberPolicy::Halt));
+        assert!("crash".parse::<InvalidNumberPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parse_overflow_policy() {
+        assert_eq!("wrap".parse(), Ok(OverflowPolicy::Wrap));
+        assert_eq!("saturate".parse(), Ok(OverflowPolicy::Saturate));
+        assert_eq!("ignore".parse(), Ok(OverflowPolicy::Ignore));
+        assert_eq!("halt".parse(), Ok(OverflowPolicy::Halt));
+        assert!("panic".parse::<OverflowPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parse_dialect() {
+        assert_eq!("spec".parse(), Ok(Dialect::spec()));
+        assert_eq!("npiet".parse(), Ok(Dialect::npiet()));
+        assert_eq!("legacy".parse(), Ok(Dialect::legacy()));
+        assert_eq!(Dialect::default(), Dialect::spec());
+        assert!("piet".parse::<Dialect>().is_err());
+    }
+}
@@ -1,6 +1,11 @@
-use crate::chooser::Chooser;
+use parse::chooser::Chooser;
 use parse::direction::Direction;
 
+use crate::options::{Dialect, OverflowPolicy};
+use crate::step::ExitReason;
+use crate::stream::{CountedInput, PietInput, PietOutput};
+use crate::value::Value;
+
 /// The state of a Piet program.
 ///
 /// # Parameters
@@ -10,30 +15,81 @@ use parse::direction::Direction;
 /// * `chooser` - The secondary direction of the pointer.
 /// * `stack` - The stack for storing data values.
 /// * `termination_counter` - The number of times that the program has failed to advance.
-/// * `stdin` - Any stdin piped to the program.
-#[derive(Debug)]
+/// * `input` - Where the program reads characters and numbers from.
+/// * `output` - Where the program writes characters and numbers to.
+/// * `dialect` - How to resolve the spec's ambiguities.
+/// * `overflow` - What happens when arithmetic overflows.
+/// * `output_bytes` - The number of bytes of output written so far.
+/// * `stopped` - Why the program was stopped early, if it was.
 pub struct State {
     pub(crate) pointer: (usize, usize),
     pub(crate) direction: Direction,
     pub(crate) chooser: Chooser,
-    pub(crate) stack: Vec<isize>,
+    pub(crate) stack: Vec<Value>,
+    pub(crate) termination_counter: u8,
+    pub(crate) input: CountedInput,
+    pub(crate) output: Box<dyn PietOutput>,
+    pub(crate) dialect: Dialect,
+    pub(crate) overflow: OverflowPolicy,
+    pub(crate) output_bytes: usize,
+    pub(crate) stopped: Option<ExitReason>,
+}
+
+/// Everything about a running program which determines what it does next, apart from its input.
+///
+/// # Parameters
+///
+/// * `pointer` - The (row, column) coordinates of the direction pointer.
+/// * `direction` - The primary direction of the pointer.
+/// * `chooser` - The secondary direction of the pointer.
+/// * `termination_counter` - The number of times that the program has failed to advance.
+/// * `stack` - The stack's contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    pub(crate) pointer: (usize, usize),
+    pub(crate) direction: Direction,
+    pub(crate) chooser: Chooser,
     pub(crate) termination_counter: u8,
-    pub(crate) stdin: Vec<char>,
+    pub(crate) stack: Vec<Value>,
 }
 
 impl State {
-    /// NOTE: Reverses `stdin` as we want to simulate pushing each element onto the stack.
-    pub fn new(stdin: Vec<char>) -> Self {
+    pub fn new(input: Box<dyn PietInput>, output: Box<dyn PietOutput>) -> Self {
         Self {
             pointer: (0, 0),
             direction: Direction::Right,
             chooser: Chooser::Left,
             stack: Vec::new(),
             termination_counter: 0,
-            stdin: stdin.into_iter().rev().collect(),
+            input: CountedInput::new(input),
+            output,
+            dialect: Dialect::default(),
+            overflow: OverflowPolicy::Ignore,
+            output_bytes: 0,
+            stopped: None,
         }
     }
 
+    /// Captures the parts of the state which determine what the program does next.
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pointer: self.pointer,
+            direction: self.direction,
+            chooser: self.chooser,
+            termination_counter: self.termination_counter,
+            stack: self.stack.clone(),
+        }
+    }
+
+    /// Whether the state is the same as when `snapshot` was taken, without having to take another one.
+    pub(crate) fn matches(&self, snapshot: &Snapshot) -> bool {
+        self.pointer == snapshot.pointer
+            && self.direction == snapshot.direction
+            && self.chooser == snapshot.chooser
+            && self.termination_counter == snapshot.termination_counter
+            && self.stack == snapshot.stack
+    }
+
     /// When there's a collision with the edge of the program or a black codel, advance the direction/chooser.
     /// For even attempts, advance the chooser direction.
     /// For odd attempts, alternate the pointer direction.
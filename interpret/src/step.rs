@@ -0,0 +1,92 @@
+use std::fmt;
+
+use parse::chooser::Chooser;
+use parse::direction::Direction;
+use parse::region::RegionId;
+
+use crate::command::{Command, Io, Skip};
+
+/// What happened to the command selected when moving between two regions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandResult {
+    /// The command was carried out.
+    Executed(Command),
+    /// The command was ignored for the given reason.
+    Skipped(Command, Skip),
+}
+
+/// Everything that happened during a single step of the interpreter.
+///
+/// # Parameters
+///
+/// * `left` - The region that the pointer started in.
+/// * `entered` - The region that the pointer moved into, or `None` if it was blocked.
+/// * `command` - The command selected by the move, if any (moves into or through white never select one).
+/// * `direction` - The direction pointer before and after the step.
+/// * `chooser` - The codel chooser before and after the step.
+/// * `io` - Any input read or output written by the command.
+/// * `halted` - Whether the program has finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepResult {
+    pub left: RegionId,
+    pub entered: Option<RegionId>,
+    pub command: Option<CommandResult>,
+    pub direction: (Direction, Direction),
+    pub chooser: (Chooser, Chooser),
+    pub io: Option<Io>,
+    pub halted: bool,
+}
+
+/// Why a program stopped running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The program finished normally.
+    Halted,
+    /// The program ran for the maximum number of steps.
+    StepLimit,
+    /// The stack grew deeper than the maximum depth.
+    StackLimit,
+    /// The program wrote more than the maximum amount of output.
+    OutputLimit,
+    /// The program ran for longer than the timeout.
+    Timeout,
+    /// The program tried to read input after it had all been used up.
+    InputExhausted,
+    /// The program tried to read a number from input that wasn't one.
+    InvalidInput,
+    /// An arithmetic command overflowed while leaving the given region.
+    Overflow { region: RegionId, command: Command },
+    /// The program got stuck repeating the same steps forever, passing through these `regions` in order at each one.
+    Loop { regions: Vec<RegionId> },
+}
+
+impl fmt::Display for ExitReason {
+    /// Writes a short description of why the program stopped, e.g. `reached the step limit`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitReason::Halted => write!(f, "halted"),
+            ExitReason::StepLimit => write!(f, "reached the step limit"),
+            ExitReason::StackLimit => write!(f, "the stack grew past its maximum depth"),
+            ExitReason::OutputLimit => write!(f, "wrote more than the maximum output"),
+            ExitReason::Timeout => write!(f, "timed out"),
+            ExitReason::InputExhausted => write!(f, "ran out of input"),
+            ExitReason::InvalidInput => write!(f, "read input that wasn't a number"),
+            ExitReason::Overflow { command, .. } => write!(f, "{} overflowed", command),
+            ExitReason::Loop { regions } => {
+                write!(f, "stuck in a loop of {} steps", regions.len())
+            }
+        }
+    }
+}
+
+/// The outcome of running a program.
+///
+/// # Parameters
+///
+/// * `reason` - Why the program stopped running.
+/// * `steps` - The number of steps that the program took.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunResult {
+    pub reason: ExitReason,
+    pub steps: u64,
+}
@@ -0,0 +1,277 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Read, Stdin, Stdout, Write};
+use std::rc::Rc;
+
+/// A source of characters for the `in_char` and `in_number` commands.
+pub trait PietInput {
+    /// Removes and returns the next character, or `None` if there's no input left.
+    fn read_char(&mut self) -> Option<char>;
+
+    /// Returns the next character without removing it, or `None` if there's no input left.
+    fn peek_char(&mut self) -> Option<char>;
+}
+
+/// A destination for the text written by the `out_char` and `out_number` commands.
+pub trait PietOutput {
+    /// Writes some text.
+    fn write_str(&mut self, text: &str) -> std::io::Result<()>;
+}
+
+/// Input that has been collected in advance.
+#[derive(Debug, Default)]
+pub struct BufferInput {
+    chars: VecDeque<char>,
+}
+
+impl From<&str> for BufferInput {
+    fn from(from: &str) -> Self {
+        BufferInput {
+            chars: from.chars().collect(),
+        }
+    }
+}
+
+impl PietInput for BufferInput {
+    fn read_char(&mut self) -> Option<char> {
+        self.chars.pop_front()
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.chars.front().copied()
+    }
+}
+
+/// Output that's captured in memory.
+///
+/// Clones share the same buffer so that output can still be inspected after handing one to an interpreter.
+#[derive(Clone, Debug, Default)]
+pub struct BufferOutput {
+    text: Rc<RefCell<String>>,
+}
+
+impl BufferOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything that has been written so far.
+    pub fn contents(&self) -> String {
+        self.text.borrow().clone()
+    }
+}
+
+impl PietOutput for BufferOutput {
+    fn write_str(&mut self, text: &str) -> std::io::Result<()> {
+        self.text.borrow_mut().push_str(text);
+        Ok(())
+    }
+}
+
+/// Input decoded as UTF-8 from any reader, one character at a time.
+///
+/// Read errors and invalid UTF-8 are treated as the end of the input, and nothing more is read after them.
+pub struct ReadInput<R: Read> {
+    reader: R,
+    peeked: Option<char>,
+    done: bool,
+}
+
+impl<R: Read> ReadInput<R> {
+    pub fn new(reader: R) -> Self {
+        ReadInput {
+            reader,
+            peeked: None,
+            done: false,
+        }
+    }
+
+    /// Decodes the next character from the reader, or stays at the end of the input once it's been reached.
+    fn decode_char(&mut self) -> Option<char> {
+        if self.done {
+            return None;
+        }
+        let decoded = self.try_decode_char();
+        self.done = decoded.is_none();
+        decoded
+    }
+
+    /// Decodes the next character from the reader, returning `None` at the end of the input or on an error.
+    fn try_decode_char(&mut self) -> Option<char> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes[..1]).ok()?;
+        // The number of leading ones in the first byte is the length of a multi-byte sequence
+        let len = match bytes[0].leading_ones() {
+            0 => 1,
+            len @ 2..=4 => len as usize,
+            _ => return None,
+        };
+        self.reader.read_exact(&mut bytes[1..len]).ok()?;
+        std::str::from_utf8(&bytes[..len]).ok()?.chars().next()
+    }
+}
+
+impl ReadInput<Stdin> {
+    /// Input read from stdin.
+    pub fn stdin() -> Self {
+        Self::new(std::io::stdin())
+    }
+}
+
+impl<R: Read> PietInput for ReadInput<R> {
+    fn read_char(&mut self) -> Option<char> {
+        self.peeked.take().or_else(|| self.decode_char())
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        if self.peeked.is_none() {
+            self.peeked = self.decode_char();
+        }
+        self.peeked
+    }
+}
+
+/// Output written to any writer, flushed after every write so that it appears immediately.
+pub struct WriteOutput<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> WriteOutput<W> {
+    pub fn new(writer: W) -> Self {
+        WriteOutput { writer }
+    }
+}
+
+impl WriteOutput<Stdout> {
+    /// Output written to stdout.
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> PietOutput for WriteOutput<W> {
+    fn write_str(&mut self, text: &str) -> std::io::Result<()> {
+        self.writer.write_all(text.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+/// Wraps another input to count how many characters have been taken from it.
+///
+/// Commands can consume input without reporting any I/O, e.g. when skipping whitespace or invalid numbers, so this is
+/// how the interpreter knows that its input has changed.
+pub(crate) struct CountedInput {
+    input: Box<dyn PietInput>,
+    pub(crate) chars_read: u64,
+}
+
+impl CountedInput {
+    pub(crate) fn new(input: Box<dyn PietInput>) -> Self {
+        CountedInput {
+            input,
+            chars_read: 0,
+        }
+    }
+}
+
+impl PietInput for CountedInput {
+    fn read_char(&mut self) -> Option<char> {
+        let char = self.input.read_char();
+        if char.is_some() {
+            self.chars_read += 1;
+        }
+        char
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.input.peek_char()
+    }
+}
+
+#[cfg(test)]
+mod test_stream {
+    use super::*;
+
+    #[test]
+    fn test_buffer_input() {
+        let mut input = BufferInput::from("ab");
+        assert_eq!(input.peek_char(), Some('a'));
+        assert_eq!(input.read_char(), Some('a'));
+        assert_eq!(input.read_char(), Some('b'));
+        assert_eq!(input.peek_char(), None);
+        assert_eq!(input.read_char(), None);
+    }
+
+    #[test]
+    fn test_buffer_output() {
+        let output = BufferOutput::new();
+        let mut writer = output.clone();
+        writer.write_str("hello ").unwrap();
+        writer.write_str("world").unwrap();
+        assert_eq!(output.contents(), "hello world");
+    }
+
+    #[test]
+    fn test_read_input() {
+        let mut input = ReadInput::new("aé€😀".as_bytes());
+        assert_eq!(input.peek_char(), Some('a'));
+        assert_eq!(input.peek_char(), Some('a'));
+        assert_eq!(input.read_char(), Some('a'));
+        assert_eq!(input.read_char(), Some('é'));
+        assert_eq!(input.read_char(), Some('€'));
+        assert_eq!(input.read_char(), Some('😀'));
+        assert_eq!(input.read_char(), None);
+    }
+
+    #[test]
+    fn test_read_input_is_lazy() {
+        /// A reader that hands out one byte per read and counts how often it was called.
+        struct CountingReader<'a>(&'a [u8], std::rc::Rc<std::cell::Cell<usize>>);
+
+        impl Read for CountingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.1.set(self.1.get() + 1);
+                (&mut self.0).take(1).read(buf)
+            }
+        }
+
+        let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut input = ReadInput::new(CountingReader(b"1\n2\n", reads.clone()));
+        assert_eq!(reads.get(), 0);
+        assert_eq!(input.read_char(), Some('1'));
+        assert_eq!(reads.get(), 1);
+        assert_eq!(input.read_char(), Some('\n'));
+        assert_eq!(input.read_char(), Some('2'));
+        assert_eq!(input.read_char(), Some('\n'));
+        assert_eq!(input.read_char(), None);
+        assert_eq!(input.read_char(), None);
+    }
+
+    #[test]
+    fn test_read_input_invalid() {
+        let mut input = ReadInput::new(&[0xFF, b'a'][..]);
+        assert_eq!(input.peek_char(), None);
+        // The input stays at its end rather than carrying on after the invalid byte
+        assert_eq!(input.read_char(), None);
+        assert_eq!(input.read_char(), None);
+        assert_eq!(input.peek_char(), None);
+    }
+
+    #[test]
+    fn test_counted_input() {
+        let mut input = CountedInput::new(Box::new(BufferInput::from("ab")));
+        assert_eq!(input.peek_char(), Some('a'));
+        assert_eq!(input.chars_read, 0);
+        assert_eq!(input.read_char(), Some('a'));
+        assert_eq!(input.read_char(), Some('b'));
+        assert_eq!(input.read_char(), None);
+        assert_eq!(input.chars_read, 2);
+    }
+
+    #[test]
+    fn test_write_output() {
+        let mut bytes = Vec::new();
+        WriteOutput::new(&mut bytes).write_str("hi").unwrap();
+        assert_eq!(bytes, b"hi");
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt::Write;
+
+use parse::chooser::Chooser;
+use parse::color::Color;
+use parse::direction::Direction;
+
+use crate::command::Command;
+use crate::interpreter::Interpreter;
+use crate::step::{CommandResult, StepResult};
+
+/// Describes each step in the same format as npiet's `-t` flag so that the two can be diffed line by line.
+///
+/// # Parameters
+///
+/// * `moves` - The number of steps traced so far, which is how npiet numbers its steps.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    moves: u64,
+}
+
+impl Tracer {
+    /// Describes a step that was just taken by the interpreter, e.g.
+    ///
+    /// ```text
+    ///
+    /// trace: step 0  (0,0/r,l lR -> 1,0/r,l nR):
+    /// action: push, value 1
+    /// trace: stack (1 values): 1
+    /// ```
+    ///
+    /// Like npiet, each step starts with a blank line and coordinates are (x, y) rather than (row, column).
+    /// Commands which were skipped, e.g. because the stack was too short, are traced the same way as executed ones.
+    /// Steps where the pointer was blocked aren't traced by npiet so they return `None`.
+    pub fn trace(&mut self, interpreter: &Interpreter, result: &StepResult) -> Option<String> {
+        result.entered?;
+        let program = interpreter.program();
+        let left = program.region(result.left);
+        let (direction, chooser) = (result.direction.0, result.chooser.0);
+        let exit = left.exit(direction, chooser).codel;
+        let next = interpreter.pointer();
+        // Commands change the DP/CC after the pointer has moved but sliding through white changes it along the way
+        let (next_direction, next_chooser) = match result.command {
+            Some(_) => (direction, chooser),
+            None => (result.direction.1, result.chooser.1),
+        };
+
+        let mut trace = format!(
+            "\ntrace: step {}  ({},{}/{},{} {} -> {},{}/{},{} {}):\n",
+            self.moves,
+            exit.1,
+            exit.0,
+            direction_name(direction),
+            chooser_name(chooser),
+            color_name(&left.color),
+            next.1,
+            next.0,
+            direction_name(next_direction),
+            chooser_name(next_chooser),
+            color_name(program.color_at(next)),
+        );
+        match &result.command {
+            Some(CommandResult::Executed(Command::Push)) => {
+                writeln!(trace, "action: push, value {}", left.size).unwrap()
+            }
+            Some(CommandResult::Executed(command) | CommandResult::Skipped(command, _)) => {
+                writeln!(trace, "action: {}", command_name(*command)).unwrap()
+            }
+            None => (),
+        }
+        match interpreter.stack() {
+            [] => trace.push_str("trace: stack is empty\n"),
+            stack => {
+                write!(trace, "trace: stack ({} values):", stack.len()).unwrap();
+                for value in stack.iter().rev() {
+                    write!(trace, " {}", value).unwrap();
+                }
+                trace.push('\n');
+            }
+        }
+        self.moves += 1;
+        Some(trace)
+    }
+}
+
+/// The name npiet gives to each command.
+fn command_name(command: Command) -> &'static str {
+    match command {
+        Command::Push => "push",
+        Command::Pop => "pop",
+        Command::Add => "add",
+        Command::Subtract => "sub",
+        Command::Multiply => "mul",
+        Command::Divide => "div",
+        Command::Modulo => "mod",
+        Command::Not => "not",
+        Command::Greater => "greater",
+        Command::Pointer => "pointer",
+        Command::Switch => "switch",
+        Command::Duplicate => "dup",
+        Command::Roll => "roll",
+        Command::InNumber => "in(number)",
+        Command::InChar => "in(char)",
+        Command::OutNumber => "out(number)",
+        Command::OutChar => "out(char)",
+    }
+}
+
+/// npiet's abbreviation for a color, e.g. `lR` for light red or `WW` for white.
+fn color_name(color: &Color) -> String {
+    match color {
+        Color::Color { hue, lightness } => format!(
+            "{}{}",
+            ["l", "n", "d"][*lightness as usize],
+            ["R", "Y", "G", "C", "B", "M"][*hue as usize]
+        ),
+        Color::Black => "BB".to_string(),
+        Color::White => "WW".to_string(),
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "u",
+        Direction::Right => "r",
+        Direction::Down => "d",
+        Direction::Left => "l",
+    }
+}
+
+fn chooser_name(chooser: Chooser) -> &'static str {
+    match chooser {
+        Chooser::Left => "l",
+        Chooser::Right => "r",
+    }
+}
+
+#[cfg(test)]
+mod test_trace {
+    use super::*;
+    use crate::step::ExitReason;
+    use crate::stream::{BufferInput, BufferOutput};
+    use parse::program::Program;
+
+    #[test]
+    fn test_trace() {
+        // light red -> red pushes 1 then red -> dark red pushes 1 again
+        let colors = vec![vec![
+            Color::Color {
+                hue: 0,
+                lightness: 0,
+            },
+            Color::Color {
+                hue: 0,
+                lightness: 1,
+            },
+            Color::Color {
+                hue: 0,
+                lightness: 2,
+            },
+        ]];
+        let mut interpreter =
+            Interpreter::new(Program::new(colors, 1, 3)).with_input(BufferInput::default());
+        let mut tracer = Tracer::default();
+
+        let result = interpreter.step();
+        assert_eq!(
+            tracer.trace(&interpreter, &result).as_deref(),
+            Some("\ntrace: step 0  (0,0/r,l lR -> 1,0/r,l nR):\naction: push, value 1\ntrace: stack (1 values): 1\n")
+        );
+        let result = interpreter.step();
+        assert_eq!(
+            tracer.trace(&interpreter, &result).as_deref(),
+            Some("\ntrace: step 1  (1,0/r,l nR -> 2,0/r,l dR):\naction: push, value 1\ntrace: stack (2 values): 1 1\n")
+        );
+        // dark red is blocked to the right, which npiet doesn't trace
+        let result = interpreter.step();
+        assert_eq!(tracer.trace(&interpreter, &result), None);
+    }
+
+    #[test]
+    fn test_trace_skipped_and_white() {
+        // light red -> dark red pops an empty stack, then dark red -> white -> red does nothing
+        let colors = vec![vec![
+            Color::Color {
+                hue: 0,
+                lightness: 0,
+            },
+            Color::Color {
+                hue: 0,
+                lightness: 2,
+            },
+            Color::White,
+            Color::Color {
+                hue: 0,
+                lightness: 1,
+            },
+        ]];
+        let mut interpreter =
+            Interpreter::new(Program::new(colors, 1, 4)).with_input(BufferInput::default());
+        let mut tracer = Tracer::default();
+
+        let result = interpreter.step();
+        assert_eq!(
+            tracer.trace(&interpreter, &result).as_deref(),
+            Some("\ntrace: step 0  (0,0/r,l lR -> 1,0/r,l dR):\naction: pop\ntrace: stack is empty\n")
+        );
+        let result = interpreter.step();
+        assert_eq!(
+            tracer.trace(&interpreter, &result).as_deref(),
+            Some("\ntrace: step 1  (1,0/r,l dR -> 3,0/r,l nR):\ntrace: stack is empty\n")
+        );
+    }
+
+    /// Runs an ascii-piet program to completion, checking its output and comparing its trace line by line with a fixture.
+    fn assert_trace_matches(text: &str, output: &str, fixture: &str) {
+        let program = Program::from_text(text).unwrap();
+        let buffer = BufferOutput::new();
+        let mut interpreter = Interpreter::new(program)
+            .with_input(BufferInput::default())
+            .with_output(buffer.clone());
+        let mut tracer = Tracer::default();
+        let mut trace = String::new();
+        let result = interpreter.run_with(|interpreter, result| {
+            if let Some(step) = tracer.trace(interpreter, result) {
+                trace.push_str(&step);
+            }
+        });
+        assert_eq!(result.reason, ExitReason::Halted);
+        assert_eq!(buffer.contents(), output);
+
+        for (line, (actual, expected)) in trace.lines().zip(fixture.lines()).enumerate() {
+            assert_eq!(actual, expected, "line {}", line + 1);
+        }
+        assert_eq!(trace.lines().count(), fixture.lines().count());
+    }
+
+    #[test]
+    fn test_trace_fixture() {
+        // Pushes 5 and prints it, then is trapped in the dark magenta region
+        assert_trace_matches(
+            "aaaaagr\nssssrrr\n",
+            "5",
+            include_str!("../fixtures/print_five.trace"),
+        );
+        // The same, but pushing 2 after passing through a white codel
+        assert_trace_matches(
+            "aataagr\nssssrrr\n",
+            "2",
+            include_str!("../fixtures/white_slide.trace"),
+        );
+    }
+}
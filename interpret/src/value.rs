@@ -0,0 +1,22 @@
+/// A value on the stack.
+///
+/// With the `bigint` feature enabled values have arbitrary precision, otherwise they're machine-sized integers.
+#[cfg(not(feature = "bigint"))]
+pub type Value = isize;
+
+/// A value on the stack.
+///
+/// With the `bigint` feature enabled values have arbitrary precision, otherwise they're machine-sized integers.
+#[cfg(feature = "bigint")]
+pub type Value = num_bigint::BigInt;
+
+/// Converts a machine-sized integer into a stack value.
+pub(crate) fn value(from: isize) -> Value {
+    num_traits::FromPrimitive::from_isize(from).unwrap()
+}
+
+/// Converts a list of machine-sized integers into stack values.
+#[cfg(test)]
+pub(crate) fn values(from: &[isize]) -> Vec<Value> {
+    from.iter().map(|&from| value(from)).collect()
+}
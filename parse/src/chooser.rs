@@ -1,13 +1,18 @@
-use parse::direction::Direction;
+use crate::direction::Direction;
 
 /// Possible directions for the direction chooser.
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub(crate) enum Chooser {
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Chooser {
     Left,
     Right,
 }
 
 impl Chooser {
+    /// Both chooser directions
+    pub fn all() -> impl Iterator<Item = Chooser> {
+        vec![Chooser::Left, Chooser::Right].into_iter()
+    }
+
     /// The next/opposite direction
     pub fn next(&self) -> Self {
         match self {
@@ -1,8 +1,8 @@
 use crate::color::Color;
-use crate::region::Region;
+use crate::region::RegionId;
 
 #[derive(Debug, PartialEq)]
 pub struct Codel {
     pub color: Color,
-    pub region: Region,
+    pub region: RegionId,
 }
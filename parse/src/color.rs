@@ -1,8 +1,149 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
 use image::{Pixel, Rgba};
 
 const HUE_CYCLE_SIZE: u8 = 6;
 const LIGHTNESS_CYCLE_SIZE: u8 = 3;
 
+/// The names of each hue in order.
+const HUE_NAMES: [&str; 6] = ["red", "yellow", "green", "cyan", "blue", "magenta"];
+/// The names of each lightness in order, where neutral colors are referred to by their hue alone.
+const LIGHTNESS_NAMES: [&str; 3] = ["light", "", "dark"];
+
+/// Every color in the Piet palette along with its canonical (red, green, blue) value.
+pub(crate) const PALETTE: [((u8, u8, u8), Color); 20] = [
+    (
+        (0xFF, 0xC0, 0xC0),
+        Color::Color {
+            hue: 0,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xFF, 0xFF, 0xC0),
+        Color::Color {
+            hue: 1,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xC0, 0xFF, 0xC0),
+        Color::Color {
+            hue: 2,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xC0, 0xFF, 0xFF),
+        Color::Color {
+            hue: 3,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xC0, 0xC0, 0xFF),
+        Color::Color {
+            hue: 4,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xFF, 0xC0, 0xFF),
+        Color::Color {
+            hue: 5,
+            lightness: 0,
+        },
+    ),
+    (
+        (0xFF, 0x00, 0x00),
+        Color::Color {
+            hue: 0,
+            lightness: 1,
+        },
+    ),
+    (
+        (0xFF, 0xFF, 0x00),
+        Color::Color {
+            hue: 1,
+            lightness: 1,
+        },
+    ),
+    (
+        (0x00, 0xFF, 0x00),
+        Color::Color {
+            hue: 2,
+            lightness: 1,
+        },
+    ),
+    (
+        (0x00, 0xFF, 0xFF),
+        Color::Color {
+            hue: 3,
+            lightness: 1,
+        },
+    ),
+    (
+        (0x00, 0x00, 0xFF),
+        Color::Color {
+            hue: 4,
+            lightness: 1,
+        },
+    ),
+    (
+        (0xFF, 0x00, 0xFF),
+        Color::Color {
+            hue: 5,
+            lightness: 1,
+        },
+    ),
+    (
+        (0xC0, 0x00, 0x00),
+        Color::Color {
+            hue: 0,
+            lightness: 2,
+        },
+    ),
+    (
+        (0xC0, 0xC0, 0x00),
+        Color::Color {
+            hue: 1,
+            lightness: 2,
+        },
+    ),
+    (
+        (0x00, 0xC0, 0x00),
+        Color::Color {
+            hue: 2,
+            lightness: 2,
+        },
+    ),
+    (
+        (0x00, 0xC0, 0xC0),
+        Color::Color {
+            hue: 3,
+            lightness: 2,
+        },
+    ),
+    (
+        (0x00, 0x00, 0xC0),
+        Color::Color {
+            hue: 4,
+            lightness: 2,
+        },
+    ),
+    (
+        (0xC0, 0x00, 0xC0),
+        Color::Color {
+            hue: 5,
+            lightness: 2,
+        },
+    ),
+    ((0xFF, 0xFF, 0xFF), Color::White),
+    ((0x00, 0x00, 0x00), Color::Black),
+];
+
 /// A Piet program is composed of a grid of colors.
 ///
 /// Codels are one of black, white, or one of 18 different colors.
@@ -17,11 +158,13 @@ const LIGHTNESS_CYCLE_SIZE: u8 = 3;
 ///
 /// The hue cycle is:
 /// - red -> yellow -> green -> cyan -> blue -> magenta -> red
+///
 /// Which is represented internally as:
 /// - 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 0
 ///
 /// The lightness cycle is:
 /// - light -> neutral -> dark -> light
+///
 /// Which is represented internally as:
 /// - 0 -> 1 -> 2 -> 0
 #[derive(Debug, PartialEq, Clone)]
@@ -32,6 +175,11 @@ pub enum Color {
 }
 
 impl Color {
+    /// Every color in the palette, starting with the 18 hue/lightness colors followed by white and black.
+    pub fn all() -> impl Iterator<Item = Color> {
+        PALETTE.iter().map(|(_, color)| color.clone())
+    }
+
     /// Returns the distance between two values along a directed cycle.
     fn cyclic_distance(current: &u8, next: &u8, cycle_size: &u8) -> u8 {
         let change = next.checked_sub(*current);
@@ -54,90 +202,103 @@ impl Color {
             _ => None,
         }
     }
+
+    /// Returns the color that's `delta_hue` and `delta_lightness` further along each cycle, the inverse of `compare`.
+    /// Black and white aren't on either cycle so they can't be shifted.
+    pub fn shift(&self, delta_hue: u8, delta_lightness: u8) -> Option<Color> {
+        // Reduce the deltas first so that large ones can't overflow
+        match self {
+            Color::Color { hue, lightness } => Some(Color::Color {
+                hue: (hue + delta_hue % HUE_CYCLE_SIZE) % HUE_CYCLE_SIZE,
+                lightness: (lightness + delta_lightness % LIGHTNESS_CYCLE_SIZE)
+                    % LIGHTNESS_CYCLE_SIZE,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the canonical (red, green, blue) value of the color.
+    pub fn to_rgb(&self) -> (u8, u8, u8) {
+        PALETTE
+            .iter()
+            .find(|(_, color)| color == self)
+            .map(|(rgb, _)| *rgb)
+            .unwrap()
+    }
+
+    /// Returns the palette color closest to an arbitrary (red, green, blue) value along with its euclidean distance.
+    pub fn nearest(rgb: (u8, u8, u8)) -> (Color, f64) {
+        let distance = |(r, g, b): (u8, u8, u8)| {
+            let channel = |from: u8, to: u8| (f64::from(from) - f64::from(to)).powi(2);
+            (channel(rgb.0, r) + channel(rgb.1, g) + channel(rgb.2, b)).sqrt()
+        };
+        PALETTE
+            .iter()
+            .map(|(p_rgb, color)| (color.clone(), distance(*p_rgb)))
+            .min_by(|(_, one), (_, two)| one.partial_cmp(two).unwrap())
+            .unwrap()
+    }
 }
 
-impl From<Rgba<u8>> for Color {
-    fn from(from: Rgba<u8>) -> Self {
-        match from.to_rgb().channels4() {
-            (0xFF, 0xC0, 0xC0, ..) => Color::Color {
-                hue: 0,
-                lightness: 0,
-            },
-            (0xFF, 0xFF, 0xC0, ..) => Color::Color {
-                hue: 1,
-                lightness: 0,
-            },
-            (0xC0, 0xFF, 0xC0, ..) => Color::Color {
-                hue: 2,
-                lightness: 0,
-            },
-            (0xC0, 0xFF, 0xFF, ..) => Color::Color {
-                hue: 3,
-                lightness: 0,
-            },
-            (0xC0, 0xC0, 0xFF, ..) => Color::Color {
-                hue: 4,
-                lightness: 0,
-            },
-            (0xFF, 0xC0, 0xFF, ..) => Color::Color {
-                hue: 5,
-                lightness: 0,
-            },
-            (0xFF, 0x00, 0x00, ..) => Color::Color {
-                hue: 0,
-                lightness: 1,
-            },
-            (0xFF, 0xFF, 0x00, ..) => Color::Color {
-                hue: 1,
-                lightness: 1,
-            },
-            (0x00, 0xFF, 0x00, ..) => Color::Color {
-                hue: 2,
-                lightness: 1,
-            },
-            (0x00, 0xFF, 0xFF, ..) => Color::Color {
-                hue: 3,
-                lightness: 1,
-            },
-            (0x00, 0x00, 0xFF, ..) => Color::Color {
-                hue: 4,
-                lightness: 1,
-            },
-            (0xFF, 0x00, 0xFF, ..) => Color::Color {
-                hue: 5,
-                lightness: 1,
-            },
-            (0xC0, 0x00, 0x00, ..) => Color::Color {
-                hue: 0,
-                lightness: 2,
-            },
-            (0xC0, 0xC0, 0x00, ..) => Color::Color {
-                hue: 1,
-                lightness: 2,
-            },
-            (0x00, 0xC0, 0x00, ..) => Color::Color {
-                hue: 2,
-                lightness: 2,
-            },
-            (0x00, 0xC0, 0xC0, ..) => Color::Color {
-                hue: 3,
-                lightness: 2,
-            },
-            (0x00, 0x00, 0xC0, ..) => Color::Color {
-                hue: 4,
-                lightness: 2,
-            },
-            (0xC0, 0x00, 0xC0, ..) => Color::Color {
-                hue: 5,
-                lightness: 2,
-            },
-            (0xFF, 0xFF, 0xFF, ..) => Color::White,
-            (0x00, 0x00, 0x00, ..) => Color::Black,
-            (r, g, b, ..) => panic!("Unsupported color ({}, {}, {})!", r, g, b),
+impl TryFrom<Rgba<u8>> for Color {
+    /// The (red, green, blue) channels of a pixel that isn't in the palette.
+    type Error = (u8, u8, u8);
+
+    fn try_from(from: Rgba<u8>) -> Result<Self, Self::Error> {
+        let (r, g, b, _) = from.to_rgb().channels4();
+        PALETTE
+            .iter()
+            .find(|(rgb, _)| rgb == &(r, g, b))
+            .map(|(_, color)| color.clone())
+            .ok_or((r, g, b))
+    }
+}
+
+impl fmt::Display for Color {
+    /// Writes the human-readable name of the color, e.g. `dark cyan`, `red`, or `white`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Color { hue, lightness } => {
+                let hue = HUE_NAMES[*hue as usize];
+                match LIGHTNESS_NAMES[*lightness as usize] {
+                    "" => write!(f, "{}", hue),
+                    lightness => write!(f, "{} {}", lightness, hue),
+                }
+            }
+            Color::Black => write!(f, "black"),
+            Color::White => write!(f, "white"),
         }
     }
 }
 
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parses either a `#RRGGBB` hex code of a palette color or a color's name, e.g. `light red` or `black`.
+    /// Neutral colors may optionally be prefixed with `normal`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |index: usize| {
+                hex.get(index..index + 2)
+                    .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+            };
+            // `from_str_radix` also accepts a leading sign, so check the digits up front
+            let digits = hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit());
+            return match (digits, channel(0), channel(2), channel(4)) {
+                (true, Some(r), Some(g), Some(b)) => Color::try_from(Rgba([r, g, b, 0xFF]))
+                    .map_err(|_| format!("{} is not a palette color", s)),
+                _ => Err(format!("{} is not a valid #RRGGBB color", s)),
+            };
+        }
+
+        let name = name.strip_prefix("normal ").unwrap_or(&name);
+        Color::all()
+            .find(|color| color.to_string() == name)
+            .ok_or_else(|| format!("{} is not the name of a color", s))
+    }
+}
+
 #[cfg(test)]
 mod test_codel {
     use super::*;
@@ -179,4 +340,162 @@ mod test_codel {
         assert_eq!(color.compare(&Color::Black), None);
         assert_eq!(Color::Black.compare(&color), None);
     }
+
+    #[test]
+    fn test_shift() {
+        let color = Color::Color {
+            hue: 4,
+            lightness: 2,
+        };
+        assert_eq!(
+            color.shift(3, 2),
+            Some(Color::Color {
+                hue: 1,
+                lightness: 1
+            })
+        );
+        assert_eq!(Color::White.shift(1, 1), None);
+        assert_eq!(
+            Color::Color {
+                hue: 5,
+                lightness: 2
+            }
+            .shift(251, 255),
+            Some(Color::Color {
+                hue: 4,
+                lightness: 2
+            })
+        );
+        for next in Color::all().filter(|color| color != &Color::White && color != &Color::Black) {
+            let (delta_hue, delta_lightness) = color.compare(&next).unwrap();
+            assert_eq!(color.shift(delta_hue, delta_lightness), Some(next));
+        }
+    }
+
+    #[test]
+    fn test_to_rgb() {
+        assert_eq!(
+            Color::Color {
+                hue: 4,
+                lightness: 0
+            }
+            .to_rgb(),
+            (0xC0, 0xC0, 0xFF)
+        );
+        assert_eq!(Color::Black.to_rgb(), (0x00, 0x00, 0x00));
+        for (rgb, color) in PALETTE.iter() {
+            let (r, g, b) = color.to_rgb();
+            assert_eq!((r, g, b), *rgb);
+            assert_eq!(Color::try_from(Rgba([r, g, b, 0xFF])).as_ref(), Ok(color));
+        }
+    }
+
+    #[test]
+    fn test_all() {
+        let all: Vec<Color> = Color::all().collect();
+        assert_eq!(all.len(), 20);
+        assert!(all.contains(&Color::Black));
+        assert!(all.contains(&Color::White));
+        assert!(all.contains(&Color::Color {
+            hue: 5,
+            lightness: 2
+        }));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Color::Color {
+                hue: 3,
+                lightness: 2
+            }
+            .to_string(),
+            "dark cyan"
+        );
+        assert_eq!(
+            Color::Color {
+                hue: 0,
+                lightness: 0
+            }
+            .to_string(),
+            "light red"
+        );
+        assert_eq!(
+            Color::Color {
+                hue: 4,
+                lightness: 1
+            }
+            .to_string(),
+            "blue"
+        );
+        assert_eq!(Color::White.to_string(), "white");
+    }
+
+    #[test]
+    fn test_from_str() {
+        let dark_cyan = Color::Color {
+            hue: 3,
+            lightness: 2,
+        };
+        assert_eq!("dark cyan".parse(), Ok(dark_cyan.clone()));
+        assert_eq!("Dark Cyan".parse(), Ok(dark_cyan.clone()));
+        assert_eq!("#00C0C0".parse(), Ok(dark_cyan.clone()));
+        assert_eq!("#00c0c0".parse(), Ok(dark_cyan));
+        assert_eq!(
+            "normal magenta".parse(),
+            Ok(Color::Color {
+                hue: 5,
+                lightness: 1
+            })
+        );
+        assert_eq!("black".parse(), Ok(Color::Black));
+        assert!("#123456".parse::<Color>().is_err());
+        assert!("#12345".parse::<Color>().is_err());
+        assert!("#GGGGGG".parse::<Color>().is_err());
+        assert!("#+f+f+f".parse::<Color>().is_err());
+        assert!("#+0+0+0".parse::<Color>().is_err());
+        assert!("purple".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for color in Color::all() {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        assert_eq!(
+            Color::nearest((0xF0, 0x00, 0x00)),
+            (
+                Color::Color {
+                    hue: 0,
+                    lightness: 1
+                },
+                15.0
+            )
+        );
+        assert_eq!(Color::nearest((0xFF, 0xFF, 0xFF)), (Color::White, 0.0));
+        assert_eq!(Color::nearest((0x10, 0x10, 0x10)).0, Color::Black);
+    }
+
+    #[test]
+    fn test_try_from_rgba() {
+        assert_eq!(
+            Color::try_from(Rgba([0x00, 0xC0, 0xC0, 0xFF])),
+            Ok(Color::Color {
+                hue: 3,
+                lightness: 2
+            })
+        );
+        assert_eq!(
+            Color::try_from(Rgba([0xFF, 0xFF, 0xFF, 0xFF])),
+            Ok(Color::White)
+        );
+        assert_eq!(
+            Color::try_from(Rgba([0x12, 0x34, 0x56, 0xFF])),
+            Err((0x12, 0x34, 0x56))
+        );
+    }
 }
@@ -1,5 +1,5 @@
 /// Cardinal directions within the program.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Right,
@@ -8,6 +8,17 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// All directions in clockwise order starting from `Up`
+    pub fn all() -> impl Iterator<Item = Direction> {
+        vec![
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+        .into_iter()
+    }
+
     /// The next direction in clockwise order
     pub fn next(&self) -> Self {
         match self {
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+
+/// The ways in which loading a Piet program can fail.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The program couldn't be read.
+    Io(io::Error),
+    /// The program isn't in an image format that can be read.
+    UnsupportedFormat(ImageError),
+    /// The program is in a supported format but couldn't be decoded.
+    Decode(ImageError),
+    /// The program couldn't be encoded as an image.
+    Encode(ImageError),
+    /// The program doesn't contain any codels.
+    EmptyImage,
+    /// The image's `width` and `height` aren't a whole number of codels of size `codel_size`.
+    /// When saving, `width` and `height` are the program's size in codels, which can't be drawn at `codel_size`.
+    InvalidCodelSize {
+        codel_size: u32,
+        width: u32,
+        height: u32,
+    },
+    /// The pixel at (`row`, `col`) doesn't match the color of the rest of its codel.
+    NonUniformCodel { row: usize, col: usize },
+    /// The pixel at (`row`, `col`) is fully transparent and transparent pixels aren't allowed.
+    Transparent { row: usize, col: usize },
+    /// The pixel at (`row`, `col`) is partially transparent, so its color is ambiguous.
+    PartialTransparency { row: usize, col: usize, alpha: u8 },
+    /// The character at (`row`, `col`) of a text program doesn't represent a color.
+    InvalidCharacter {
+        row: usize,
+        col: usize,
+        character: char,
+    },
+    /// The `line`th line of a text program is blank.
+    BlankLine { line: usize },
+    /// The `row`th row of a text program has `found` codels rather than the `expected` number.
+    UnevenRows {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The pixel at (`row`, `col`) isn't one of the colors in the Piet palette.
+    InvalidColor {
+        row: usize,
+        col: usize,
+        rgb: (u8, u8, u8),
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "unable to read program: {}", err),
+            ParseError::UnsupportedFormat(err) => write!(f, "unsupported image format: {}", err),
+            ParseError::Decode(err) => write!(f, "unable to decode image: {}", err),
+            ParseError::Encode(err) => write!(f, "unable to encode image: {}", err),
+            ParseError::EmptyImage => write!(f, "program is empty"),
+            ParseError::InvalidCodelSize {
+                codel_size,
+                width,
+                height,
+            } => write!(
+                f,
+                "a {}x{} image can't be divided into codels of size {}",
+                width, height, codel_size
+            ),
+            ParseError::NonUniformCodel { row, col } => write!(
+                f,
+                "pixel at row {}, column {} doesn't match the rest of its codel",
+                row, col
+            ),
+            ParseError::Transparent { row, col } => {
+                write!(f, "pixel at row {}, column {} is transparent", row, col)
+            }
+            ParseError::PartialTransparency { row, col, alpha } => write!(
+                f,
+                "pixel at row {}, column {} is partially transparent (alpha {})",
+                row, col, alpha
+            ),
+            ParseError::InvalidCharacter {
+                row,
+                col,
+                character,
+            } => write!(
+                f,
+                "unsupported character {:?} at row {}, column {}",
+                character, row, col
+            ),
+            ParseError::BlankLine { line } => write!(f, "line {} is blank", line),
+            ParseError::UnevenRows {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} codels but expected {}",
+                row, found, expected
+            ),
+            ParseError::InvalidColor {
+                row,
+                col,
+                rgb: (r, g, b),
+            } => write!(
+                f,
+                "unsupported color #{:02X}{:02X}{:02X} at row {}, column {}",
+                r, g, b, row, col
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            ParseError::UnsupportedFormat(err)
+            | ParseError::Decode(err)
+            | ParseError::Encode(err) => Some(err),
+            ParseError::EmptyImage
+            | ParseError::InvalidCodelSize { .. }
+            | ParseError::NonUniformCodel { .. }
+            | ParseError::Transparent { .. }
+            | ParseError::PartialTransparency { .. }
+            | ParseError::InvalidCharacter { .. }
+            | ParseError::BlankLine { .. }
+            | ParseError::UnevenRows { .. }
+            | ParseError::InvalidColor { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(from: io::Error) -> Self {
+        ParseError::Io(from)
+    }
+}
+
+impl From<ImageError> for ParseError {
+    fn from(from: ImageError) -> Self {
+        match from {
+            ImageError::IoError(err) => ParseError::Io(err),
+            err @ ImageError::Unsupported(_) => ParseError::UnsupportedFormat(err),
+            err @ ImageError::Encoding(_) => ParseError::Encode(err),
+            err => ParseError::Decode(err),
+        }
+    }
+}
@@ -1,7 +1,11 @@
-#![feature(slice_group_by)]
-
+pub mod chooser;
 pub mod codel;
 pub mod color;
 pub mod direction;
+pub mod error;
+mod loader;
+pub mod options;
 pub mod program;
 pub mod region;
+pub mod text;
+mod writer;
@@ -0,0 +1,448 @@
+use std::convert::TryFrom;
+use std::io::{BufRead, Cursor, Seek};
+
+use image::io::Reader;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba};
+
+use crate::color::Color;
+use crate::error::ParseError;
+use crate::options::{CodelSize, LoadOptions, TransparencyPolicy, UnknownColorPolicy};
+use crate::program::Program;
+
+impl Program {
+    /// Loads a program from a file given its path.
+    pub fn load(path: &str) -> Result<Program, ParseError> {
+        Self::load_with_options(path, &LoadOptions::default())
+    }
+
+    /// Loads a program from a file given its path and the options to load it with.
+    pub fn load_with_options(path: &str, options: &LoadOptions) -> Result<Program, ParseError> {
+        let img = image::open(path)?;
+        Self::try_from_image_with_options(&img, options)
+    }
+
+    /// Loads a program from an image being read from `reader`, guessing its format from its contents.
+    pub fn from_reader<R: BufRead + Seek>(reader: R) -> Result<Program, ParseError> {
+        Self::from_reader_with_options(reader, &LoadOptions::default())
+    }
+
+    /// Loads a program from an image being read from `reader` given the options to load it with.
+    pub fn from_reader_with_options<R: BufRead + Seek>(
+        reader: R,
+        options: &LoadOptions,
+    ) -> Result<Program, ParseError> {
+        let img = Reader::new(reader).with_guessed_format()?.decode()?;
+        Self::try_from_image_with_options(&img, options)
+    }
+
+    /// Loads a program from an encoded image, guessing its format from its contents if `format` isn't given.
+    pub fn from_bytes(bytes: &[u8], format: Option<ImageFormat>) -> Result<Program, ParseError> {
+        Self::from_bytes_with_options(bytes, format, &LoadOptions::default())
+    }
+
+    /// Loads a program from an encoded image given the options to load it with.
+    pub fn from_bytes_with_options(
+        bytes: &[u8],
+        format: Option<ImageFormat>,
+        options: &LoadOptions,
+    ) -> Result<Program, ParseError> {
+        match format {
+            Some(format) => {
+                let img = image::load_from_memory_with_format(bytes, format)?;
+                Self::try_from_image_with_options(&img, options)
+            }
+            None => Self::from_reader_with_options(Cursor::new(bytes), options),
+        }
+    }
+
+    /// Builds a program from an already decoded image where each pixel is one codel.
+    pub fn try_from_image(img: &DynamicImage) -> Result<Program, ParseError> {
+        Self::try_from_image_with_options(img, &LoadOptions::default())
+    }
+
+    /// Builds a program from an already decoded image given the options to load it with.
+    ///
+    /// Each `codel_size` by `codel_size` block of pixels becomes one codel, and must be a single color.
+    /// If the codel size is `CodelSize::Auto` then it's inferred with `detect_codel_size`.
+    pub fn try_from_image_with_options(
+        img: &DynamicImage,
+        options: &LoadOptions,
+    ) -> Result<Program, ParseError> {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 {
+            return Err(ParseError::EmptyImage);
+        }
+
+        let pixels = pixel_colors(img, options)?;
+        let codel_size = match options.codel_size {
+            CodelSize::Auto => Self::detect_codel_size(&pixels),
+            CodelSize::Fixed(codel_size) => codel_size,
+        };
+        if codel_size == 0 || width % codel_size != 0 || height % codel_size != 0 {
+            return Err(ParseError::InvalidCodelSize {
+                codel_size,
+                width,
+                height,
+            });
+        }
+
+        let colors = downsample(&pixels, codel_size as usize)?;
+        let (rows, cols) = (colors.len(), colors[0].len());
+        Ok(Self::new(colors, rows, cols))
+    }
+
+    /// Infers the codel size of a grid of pixels as the largest size that evenly divides every run of identical colors.
+    ///
+    /// Runs are measured both horizontally and vertically, and since every row and column is made up of whole runs the result always divides the grid's dimensions.
+    pub fn detect_codel_size(pixels: &[Vec<Color>]) -> u32 {
+        let height = pixels.len();
+        let width = pixels.first().map_or(0, |row| row.len());
+
+        let horizontal = pixels.iter().map(|row| row.iter().collect::<Vec<&Color>>());
+        let vertical = (0..width).map(|col| pixels.iter().map(|row| &row[col]).collect());
+        let codel_size = horizontal
+            .chain(vertical)
+            .flat_map(|line: Vec<&Color>| {
+                line.chunk_by(|one, two| one == two)
+                    .map(|run| run.len())
+                    .collect::<Vec<usize>>()
+            })
+            .fold(gcd(width, height), gcd);
+
+        codel_size as u32
+    }
+}
+
+/// Converts every pixel in an image to a palette color, resolving any unknown or transparent ones according to `options`.
+fn pixel_colors(img: &DynamicImage, options: &LoadOptions) -> Result<Vec<Vec<Color>>, ParseError> {
+    let (width, height) = img.dimensions();
+    (0..height)
+        .map(|row| {
+            (0..width)
+                .map(|col| {
+                    pixel_color(
+                        img.get_pixel(col, row),
+                        row as usize,
+                        col as usize,
+                        &options.unknown_color,
+                        &options.transparent,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Converts a single pixel at (`row`, `col`) to a palette color.
+///
+/// Partially transparent pixels are always rejected since their color depends on whatever they'd be drawn over.
+fn pixel_color(
+    pixel: Rgba<u8>,
+    row: usize,
+    col: usize,
+    unknown_color: &UnknownColorPolicy,
+    transparent: &TransparencyPolicy,
+) -> Result<Color, ParseError> {
+    let Rgba([.., alpha]) = pixel;
+    match alpha {
+        0xFF => Color::try_from(pixel).or_else(|rgb| {
+            unknown_color
+                .resolve(rgb)
+                .ok_or(ParseError::InvalidColor { row, col, rgb })
+        }),
+        0x00 => transparent
+            .resolve()
+            .ok_or(ParseError::Transparent { row, col }),
+        alpha => Err(ParseError::PartialTransparency { row, col, alpha }),
+    }
+}
+
+/// Shrinks a grid of pixels into a grid of codels, checking that every pixel in a codel agrees.
+fn downsample(pixels: &[Vec<Color>], codel_size: usize) -> Result<Vec<Vec<Color>>, ParseError> {
+    pixels
+        .iter()
+        .enumerate()
+        .step_by(codel_size)
+        .map(|(top, _)| {
+            (0..pixels[top].len())
+                .step_by(codel_size)
+                .map(|left| {
+                    let color = &pixels[top][left];
+                    let mut block = pixels[top..top + codel_size].iter().enumerate().flat_map(
+                        |(d_row, row)| {
+                            row[left..left + codel_size]
+                                .iter()
+                                .enumerate()
+                                .map(move |(d_col, pixel)| ((top + d_row, left + d_col), pixel))
+                        },
+                    );
+                    match block.find(|(_, pixel)| pixel != &color) {
+                        Some(((row, col), _)) => Err(ParseError::NonUniformCodel { row, col }),
+                        None => Ok(color.clone()),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The greatest common divisor of two numbers.
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod test_loader {
+    use image::{ImageOutputFormat, RgbaImage};
+
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([0xFF, 0xFF, 0xFF, 0xFF]);
+    const BLACK: Rgba<u8> = Rgba([0x00, 0x00, 0x00, 0xFF]);
+
+    /// A 2x1 program of a white and a black codel encoded as a PNG.
+    fn png_bytes() -> Vec<u8> {
+        let mut img = RgbaImage::from_pixel(2, 1, WHITE);
+        img.put_pixel(1, 0, BLACK);
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(img)
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn fixed(codel_size: u32) -> LoadOptions {
+        LoadOptions {
+            codel_size: CodelSize::Fixed(codel_size),
+            ..LoadOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let program = Program::from_reader(Cursor::new(png_bytes())).unwrap();
+        assert_eq!(program.color_at((0, 0)), &Color::White);
+        assert_eq!(program.color_at((0, 1)), &Color::Black);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let bytes = png_bytes();
+        let sniffed = Program::from_bytes(&bytes, None).unwrap();
+        assert_eq!(sniffed.color_at((0, 1)), &Color::Black);
+        let explicit = Program::from_bytes(&bytes, Some(ImageFormat::Png)).unwrap();
+        assert_eq!(explicit.color_at((0, 1)), &Color::Black);
+    }
+
+    #[test]
+    fn test_from_bytes_unrecognized() {
+        assert!(matches!(
+            Program::from_bytes(b"not an image", None),
+            Err(ParseError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            Program::from_bytes(b"not an image", Some(ImageFormat::Png)),
+            Err(ParseError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_from_image() {
+        let mut img = RgbaImage::from_pixel(2, 1, WHITE);
+        img.put_pixel(1, 0, BLACK);
+        let program = Program::try_from_image(&DynamicImage::ImageRgba8(img)).unwrap();
+        assert_eq!(program.color_at((0, 0)), &Color::White);
+        assert_eq!(program.color_at((0, 1)), &Color::Black);
+    }
+
+    #[test]
+    fn test_try_from_image_invalid_color() {
+        let mut img = RgbaImage::from_pixel(3, 2, WHITE);
+        img.put_pixel(2, 1, Rgba([0x12, 0x34, 0x56, 0xFF]));
+        match Program::try_from_image(&DynamicImage::ImageRgba8(img)) {
+            Err(ParseError::InvalidColor { row, col, rgb }) => {
+                assert_eq!((row, col, rgb), (1, 2, (0x12, 0x34, 0x56)))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_try_from_image_unknown_color_policy() {
+        let mut img = RgbaImage::from_pixel(2, 1, WHITE);
+        img.put_pixel(1, 0, Rgba([0xF0, 0x00, 0x00, 0xFF]));
+        let img = DynamicImage::ImageRgba8(img);
+        let load = |unknown_color| {
+            let options = LoadOptions {
+                unknown_color,
+                ..LoadOptions::default()
+            };
+            Program::try_from_image_with_options(&img, &options)
+        };
+
+        let black = load(UnknownColorPolicy::Black).unwrap();
+        assert_eq!(black.color_at((0, 1)), &Color::Black);
+        let nearest = load(UnknownColorPolicy::Nearest { max_distance: 20.0 }).unwrap();
+        assert_eq!(
+            nearest.color_at((0, 1)),
+            &Color::Color {
+                hue: 0,
+                lightness: 1
+            }
+        );
+        assert!(matches!(
+            load(UnknownColorPolicy::Nearest { max_distance: 10.0 }),
+            Err(ParseError::InvalidColor { row: 0, col: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_try_from_image_transparency_policy() {
+        let mut img = RgbaImage::from_pixel(2, 1, WHITE);
+        img.put_pixel(1, 0, Rgba([0x00, 0x00, 0x00, 0x00]));
+        let img = DynamicImage::ImageRgba8(img);
+        let load = |transparent| {
+            let options = LoadOptions {
+                transparent,
+                ..LoadOptions::default()
+            };
+            Program::try_from_image_with_options(&img, &options)
+        };
+
+        let white = load(TransparencyPolicy::White).unwrap();
+        assert_eq!(white.color_at((0, 1)), &Color::White);
+        let black = load(TransparencyPolicy::Black).unwrap();
+        assert_eq!(black.color_at((0, 1)), &Color::Black);
+        assert!(matches!(
+            load(TransparencyPolicy::Error),
+            Err(ParseError::Transparent { row: 0, col: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_try_from_image_partial_transparency() {
+        let mut img = RgbaImage::from_pixel(2, 1, WHITE);
+        img.put_pixel(1, 0, Rgba([0xFF, 0x00, 0x00, 0x80]));
+        let options = LoadOptions {
+            transparent: TransparencyPolicy::White,
+            unknown_color: UnknownColorPolicy::White,
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            Program::try_from_image_with_options(&DynamicImage::ImageRgba8(img), &options),
+            Err(ParseError::PartialTransparency {
+                row: 0,
+                col: 1,
+                alpha: 0x80
+            })
+        ));
+    }
+
+    #[test]
+    fn test_try_from_image_empty() {
+        let img = RgbaImage::new(0, 0);
+        assert!(matches!(
+            Program::try_from_image(&DynamicImage::ImageRgba8(img)),
+            Err(ParseError::EmptyImage)
+        ));
+    }
+
+    #[test]
+    fn test_try_from_image_codel_size() {
+        let mut img = RgbaImage::from_pixel(4, 2, WHITE);
+        img.put_pixel(2, 0, BLACK);
+        img.put_pixel(3, 0, BLACK);
+        img.put_pixel(2, 1, BLACK);
+        img.put_pixel(3, 1, BLACK);
+        let program =
+            Program::try_from_image_with_options(&DynamicImage::ImageRgba8(img), &fixed(2))
+                .unwrap();
+        assert_eq!((program.rows(), program.cols()), (1, 2));
+        assert_eq!(program.color_at((0, 0)), &Color::White);
+        assert_eq!(program.color_at((0, 1)), &Color::Black);
+    }
+
+    #[test]
+    fn test_try_from_image_non_uniform_codel() {
+        let mut img = RgbaImage::from_pixel(4, 2, WHITE);
+        img.put_pixel(3, 1, BLACK);
+        match Program::try_from_image_with_options(&DynamicImage::ImageRgba8(img), &fixed(2)) {
+            Err(ParseError::NonUniformCodel { row, col }) => assert_eq!((row, col), (1, 3)),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_try_from_image_invalid_codel_size() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 3, WHITE));
+        assert!(matches!(
+            Program::try_from_image_with_options(&img, &fixed(2)),
+            Err(ParseError::InvalidCodelSize { .. })
+        ));
+        assert!(matches!(
+            Program::try_from_image_with_options(&img, &fixed(0)),
+            Err(ParseError::InvalidCodelSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_gcd() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(7, 3), 1);
+        assert_eq!(gcd(5, 0), 5);
+    }
+
+    #[test]
+    fn test_detect_codel_size() {
+        // Two codels of size 3 side by side: a 2x1 program
+        let mut img = RgbaImage::from_pixel(6, 3, WHITE);
+        for row in 0..3 {
+            for col in 3..6 {
+                img.put_pixel(col, row, BLACK);
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+        let pixels = pixel_colors(&img, &LoadOptions::default()).unwrap();
+        assert_eq!(Program::detect_codel_size(&pixels), 3);
+
+        let options = LoadOptions {
+            codel_size: CodelSize::Auto,
+            ..LoadOptions::default()
+        };
+        let program = Program::try_from_image_with_options(&img, &options).unwrap();
+        assert_eq!((program.rows(), program.cols()), (1, 2));
+    }
+
+    #[test]
+    fn test_detect_codel_size_uniform() {
+        let pixels = vec![vec![Color::White; 4]; 6];
+        assert_eq!(Program::detect_codel_size(&pixels), 2);
+    }
+
+    #[test]
+    fn test_detect_codel_size_pixel_exact() {
+        let mut pixels = vec![vec![Color::White; 4]; 4];
+        pixels[2][1] = Color::Black;
+        assert_eq!(Program::detect_codel_size(&pixels), 1);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        assert!(matches!(
+            Program::load("does/not/exist.png"),
+            Err(ParseError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_unsupported_format() {
+        assert!(matches!(
+            Program::load("Cargo.toml"),
+            Err(ParseError::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use image::ImageFormat;
+
+use crate::color::Color;
+
+/// How large each codel in an image is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodelSize {
+    /// Infer the codel size from the image.
+    Auto,
+    /// Each codel is this many pixels wide and tall.
+    Fixed(u32),
+}
+
+impl FromStr for CodelSize {
+    type Err = String;
+
+    /// Parses either `auto` or a number of pixels.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(CodelSize::Auto),
+            _ => s
+                .parse()
+                .map(CodelSize::Fixed)
+                .map_err(|_| format!("expected `auto` or a number of pixels, got `{}`", s)),
+        }
+    }
+}
+
+/// What to do with pixels whose colors aren't in the Piet palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnknownColorPolicy {
+    /// Fail to load the program.
+    Error,
+    /// Treat the pixel as white.
+    White,
+    /// Treat the pixel as black.
+    Black,
+    /// Snap the pixel to the closest palette color, failing if it's further away than `max_distance`.
+    Nearest { max_distance: f64 },
+}
+
+impl UnknownColorPolicy {
+    /// Picks the color to use for a pixel that isn't in the palette, if there is one.
+    pub fn resolve(&self, rgb: (u8, u8, u8)) -> Option<Color> {
+        match self {
+            UnknownColorPolicy::Error => None,
+            UnknownColorPolicy::White => Some(Color::White),
+            UnknownColorPolicy::Black => Some(Color::Black),
+            UnknownColorPolicy::Nearest { max_distance } => {
+                let (color, distance) = Color::nearest(rgb);
+                if distance <= *max_distance {
+                    Some(color)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for UnknownColorPolicy {
+    type Err = String;
+
+    /// Parses one of `error`, `white`, `black`, `nearest`, or `nearest:<max distance>`.
+    /// The max distance can't be negative or NaN since no color would ever be close enough.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(UnknownColorPolicy::Error),
+            "white" => Ok(UnknownColorPolicy::White),
+            "black" => Ok(UnknownColorPolicy::Black),
+            "nearest" => Ok(UnknownColorPolicy::Nearest {
+                max_distance: f64::INFINITY,
+            }),
+            _ => s
+                .strip_prefix("nearest:")
+                .and_then(|max_distance| max_distance.parse().ok())
+                .filter(|max_distance: &f64| *max_distance >= 0.0)
+                .map(|max_distance| UnknownColorPolicy::Nearest { max_distance })
+                .ok_or_else(|| {
+                    format!(
+                        "expected one of `error`, `white`, `black`, `nearest` or `nearest:<max distance>`, got `{}`",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// What to do with fully transparent pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransparencyPolicy {
+    /// Fail to load the program.
+    Error,
+    /// Treat the pixel as white.
+    White,
+    /// Treat the pixel as black.
+    Black,
+}
+
+impl TransparencyPolicy {
+    /// Picks the color to use for a fully transparent pixel, if there is one.
+    pub fn resolve(&self) -> Option<Color> {
+        match self {
+            TransparencyPolicy::Error => None,
+            TransparencyPolicy::White => Some(Color::White),
+            TransparencyPolicy::Black => Some(Color::Black),
+        }
+    }
+}
+
+impl FromStr for TransparencyPolicy {
+    type Err = String;
+
+    /// Parses one of `error`, `white`, or `black`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(TransparencyPolicy::Error),
+            "white" => Ok(TransparencyPolicy::White),
+            "black" => Ok(TransparencyPolicy::Black),
+            _ => Err(format!(
+                "expected one of `error`, `white` or `black`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// Options controlling how an image is turned into a program.
+///
+/// # Parameters
+///
+/// * `codel_size` - The width and height in pixels of a single codel.
+/// * `unknown_color` - How to treat pixels whose colors aren't in the Piet palette.
+/// * `transparent` - How to treat fully transparent pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadOptions {
+    pub codel_size: CodelSize,
+    pub unknown_color: UnknownColorPolicy,
+    pub transparent: TransparencyPolicy,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            codel_size: CodelSize::Fixed(1),
+            unknown_color: UnknownColorPolicy::Error,
+            transparent: TransparencyPolicy::Error,
+        }
+    }
+}
+
+/// Options controlling how a program is written out as an image.
+///
+/// # Parameters
+///
+/// * `codel_size` - The width and height in pixels of a single codel.
+/// * `format` - The image format to write, or `None` to pick one based on the path's extension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions {
+    pub codel_size: u32,
+    pub format: Option<ImageFormat>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            codel_size: 1,
+            format: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_options {
+    use super::*;
+
+    #[test]
+    fn test_parse_codel_size() {
+        assert_eq!("auto".parse(), Ok(CodelSize::Auto));
+        assert_eq!("10".parse(), Ok(CodelSize::Fixed(10)));
+        assert!("big".parse::<CodelSize>().is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_color_policy() {
+        assert_eq!("error".parse(), Ok(UnknownColorPolicy::Error));
+        assert_eq!("white".parse(), Ok(UnknownColorPolicy::White));
+        assert_eq!("black".parse(), Ok(UnknownColorPolicy::Black));
+        assert_eq!(
+            "nearest".parse(),
+            Ok(UnknownColorPolicy::Nearest {
+                max_distance: f64::INFINITY
+            })
+        );
+        assert_eq!(
+            "nearest:32".parse(),
+            Ok(UnknownColorPolicy::Nearest { max_distance: 32.0 })
+        );
+        assert!("nearest:far".parse::<UnknownColorPolicy>().is_err());
+        assert!("nearest:-1".parse::<UnknownColorPolicy>().is_err());
+        assert!("nearest:NaN".parse::<UnknownColorPolicy>().is_err());
+        assert!("gray".parse::<UnknownColorPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parse_transparency_policy() {
+        assert_eq!("error".parse(), Ok(TransparencyPolicy::Error));
+        assert_eq!("white".parse(), Ok(TransparencyPolicy::White));
+        assert_eq!("black".parse(), Ok(TransparencyPolicy::Black));
+        assert!("nearest".parse::<TransparencyPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_unknown_color() {
+        let rgb = (0xF0, 0x00, 0x00);
+        let red = Color::Color {
+            hue: 0,
+            lightness: 1,
+        };
+        assert_eq!(UnknownColorPolicy::Error.resolve(rgb), None);
+        assert_eq!(UnknownColorPolicy::White.resolve(rgb), Some(Color::White));
+        assert_eq!(UnknownColorPolicy::Black.resolve(rgb), Some(Color::Black));
+        assert_eq!(
+            UnknownColorPolicy::Nearest { max_distance: 15.0 }.resolve(rgb),
+            Some(red)
+        );
+        assert_eq!(
+            UnknownColorPolicy::Nearest { max_distance: 10.0 }.resolve(rgb),
+            None
+        );
+    }
+}
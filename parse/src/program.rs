@@ -1,85 +1,87 @@
-extern crate image;
-extern crate itertools;
-
 use std::collections::{HashMap, HashSet};
 
-use image::GenericImageView;
-use itertools::Itertools;
-
+use crate::chooser::Chooser;
 use crate::codel::Codel;
 use crate::color::Color;
 use crate::direction::Direction;
-use crate::region::Region;
+use crate::region::{Exit, Region, RegionId};
 
 /// A Piet program is represented as a 2d grid of Codels.
 ///
 /// # Parameters
 ///
-/// * `points` - A collection of rows of Codels and their corresponding Regions where `points[0][0]` represents the top-left point.
+/// * `points` - A collection of rows of Codels and the ids of their corresponding Regions where `points[0][0]` represents the top-left point.
+/// * `regions` - An arena holding every Region in the program, indexed by `RegionId`.
 /// * `rows` - The number of rows in the program.
 /// * `cols` - The number of cols in the program.
 #[derive(Debug)]
 pub struct Program {
     pub points: Vec<Vec<Codel>>,
+    regions: Vec<Region>,
     rows: usize,
     cols: usize,
 }
 
 impl Program {
     pub fn new(colors: Vec<Vec<Color>>, rows: usize, cols: usize) -> Program {
-        let points = Self::get_codels(&colors, &rows, &cols);
+        let (points, regions) = Self::get_codels(&colors, &rows, &cols);
 
-        Program { points, rows, cols }
+        Program {
+            points,
+            regions,
+            rows,
+            cols,
+        }
     }
 
-    /// Loads a program from a file given its path.
-    pub fn load(path: &str) -> Program {
-        let img = image::open(path).unwrap();
-        let (cols, rows) = {
-            let (r_cols, r_rows) = img.dimensions();
-            (r_cols as usize, r_rows as usize)
-        };
-        let colors: Vec<Vec<Color>> = img
-            .pixels()
-            .chunks(cols as usize)
-            .into_iter()
-            .map(|row| row.map(|(.., color)| Color::from(color)).collect())
-            .collect();
+    /// Builds a grid of Codels from a grid of Colors along with the arena of Regions that they refer to
+    fn get_codels(
+        colors: &[Vec<Color>],
+        rows: &usize,
+        cols: &usize,
+    ) -> (Vec<Vec<Codel>>, Vec<Region>) {
+        let mut regions: Vec<Region> = Vec::new();
+        // Maps coordinates to the id of the color region that they belong to
+        let mut ids: Vec<Vec<Option<RegionId>>> = vec![vec![None; *cols]; *rows];
 
-        Self::new(colors, rows, cols)
-    }
-
-    /// Builds a grid of Codels from a grid of Colors
-    fn get_codels(colors: &[Vec<Color>], rows: &usize, cols: &usize) -> Vec<Vec<Codel>> {
-        // Maps coordinates to the color region that they belong to
-        let mut regions: HashMap<(usize, usize), Region> = HashMap::new();
+        for row in 0..*rows {
+            for col in 0..*cols {
+                // Build a region if the location hasn't been seen before
+                if ids[row][col].is_none() {
+                    let id = RegionId(regions.len());
+                    let region = Self::get_region(colors, (row, col), rows, cols);
+                    for (m_row, m_col) in &region.members {
+                        ids[*m_row][*m_col] = Some(id);
+                    }
+                    regions.push(region);
+                }
+            }
+        }
 
-        // TODO: there's a lot of gross cloning to get rid of here
-        (0..*rows)
-            .map(|row| {
-                (0..*cols)
-                    .map(|col| {
-                        let location = (row, col);
-                        let color = colors[row][col].clone();
-                        // Build a region if the location hasn't been seen before
-                        let region = if let Some(region) = regions.get(&location) {
-                            region.clone()
-                        } else {
-                            let region = Self::get_region(colors, location);
-                            for member in &region.members {
-                                regions.insert(*member, region.clone());
-                            }
-                            region
-                        };
-                        Codel { color, region }
+        let points = ids
+            .into_iter()
+            .enumerate()
+            .map(|(row, id_row)| {
+                id_row
+                    .into_iter()
+                    .enumerate()
+                    .map(|(col, id)| Codel {
+                        color: colors[row][col].clone(),
+                        region: id.unwrap(),
                     })
                     .collect()
             })
-            .collect()
+            .collect();
+        (points, regions)
     }
 
-    /// Get all members of the same contiguous region of color
-    fn get_region(colors: &[Vec<Color>], point: (usize, usize)) -> Region {
+    /// Get all members of the same contiguous region of color along with its exits
+    fn get_region(
+        colors: &[Vec<Color>],
+        point: (usize, usize),
+        rows: &usize,
+        cols: &usize,
+    ) -> Region {
         let mut members = HashSet::new();
         let mut neighbors = vec![point];
         let mut seen: HashSet<(usize, usize)> = neighbors.clone().into_iter().collect();
@@ -95,7 +97,7 @@ impl Program {
                     members.insert(neighbor);
                     for n_neighbor in Self::neighbors(neighbor) {
                         if !seen.contains(&n_neighbor) {
-                            seen.insert(neighbor);
+                            seen.insert(n_neighbor);
                             neighbors.push(n_neighbor);
                         }
                     }
@@ -104,7 +106,26 @@ impl Program {
             }
         }
 
-        Region::new(members)
+        let exits = Self::get_exits(colors, &members, rows, cols);
+        Region::new(members, color.clone(), exits)
+    }
+
+    /// Get the exit for every combination of direction pointer and codel chooser
+    fn get_exits(
+        colors: &[Vec<Color>],
+        members: &HashSet<(usize, usize)>,
+        rows: &usize,
+        cols: &usize,
+    ) -> HashMap<(Direction, Chooser), Exit> {
+        Direction::all()
+            .flat_map(|direction| Chooser::all().map(move |chooser| (direction, chooser)))
+            .map(|(direction, chooser)| {
+                let codel = Region::exit_codel(members, direction, chooser.choose(direction));
+                let next = Self::step(codel, direction, rows, cols)
+                    .map(|(row, col)| ((row, col), colors[row][col].clone()));
+                ((direction, chooser), Exit { codel, next })
+            })
+            .collect()
     }
 
     /// Get all the neighbors of a given point
@@ -127,12 +148,23 @@ impl Program {
         start: (usize, usize),
         direction: T,
     ) -> Option<((usize, usize), &Color)> {
+        Self::step(start, direction.into(), &self.rows, &self.cols)
+            .map(|next| (next, self.color_at(next)))
+    }
+
+    /// Gets the adjacent point in the given direction if it's within the program's bounds.
+    fn step(
+        start: (usize, usize),
+        direction: Direction,
+        rows: &usize,
+        cols: &usize,
+    ) -> Option<(usize, usize)> {
         let (row, col) = start;
-        let maybe_next = match direction.into() {
+        match direction {
             Direction::Up => row.checked_sub(1).map(|next_row| (next_row, col)),
             Direction::Down => {
                 let next_row = row + 1;
-                if next_row < self.rows {
+                if next_row < *rows {
                     Some((next_row, col))
                 } else {
                     None
@@ -141,14 +173,23 @@ impl Program {
             Direction::Left => col.checked_sub(1).map(|next_col| (row, next_col)),
             Direction::Right => {
                 let next_col = col + 1;
-                if next_col < self.cols {
+                if next_col < *cols {
                     Some((row, next_col))
                 } else {
                     None
                 }
             }
-        };
-        maybe_next.map(|next| (next, self.color_at(next)))
+        }
+    }
+
+    /// The number of rows in the program.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns in the program.
+    pub fn cols(&self) -> usize {
+        self.cols
     }
 
     /// Gets the codel at the specified (row, column) point.
@@ -162,9 +203,24 @@ impl Program {
         &self.codel_at(point).color
     }
 
+    /// Gets the id of the region at the specified (row, column) point.
+    pub fn region_id_at(&self, point: (usize, usize)) -> RegionId {
+        self.codel_at(point).region
+    }
+
     /// Gets the region at the specified (row, column) point.
     pub fn region_at(&self, point: (usize, usize)) -> &Region {
-        &self.codel_at(point).region
+        self.region(self.codel_at(point).region)
+    }
+
+    /// Gets the region with the given id.
+    pub fn region(&self, id: RegionId) -> &Region {
+        &self.regions[id.0]
+    }
+
+    /// All regions in the program, in the order that they were discovered.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
     }
 }
 
@@ -179,29 +235,65 @@ mod test_program {
             vec![Color::White, Color::Black],
         ];
         let program = Program::new(colors, 2, 2);
+        let white: HashSet<(usize, usize)> = vec![(0, 0), (0, 1), (1, 0)].into_iter().collect();
+        let black: HashSet<(usize, usize)> = vec![(1, 1)].into_iter().collect();
+        let actual: Vec<Vec<_>> = program
+            .points
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|codel| (&codel.color, &program.region(codel.region).members))
+                    .collect()
+            })
+            .collect();
         let expected = vec![
-            vec![
-                Codel {
-                    color: Color::White,
-                    region: Region::new(vec![(0, 0), (0, 1), (1, 0)].into_iter().collect()),
-                },
-                Codel {
-                    color: Color::White,
-                    region: Region::new(vec![(0, 0), (0, 1), (1, 0)].into_iter().collect()),
-                },
-            ],
-            vec![
-                Codel {
-                    color: Color::White,
-                    region: Region::new(vec![(0, 0), (0, 1), (1, 0)].into_iter().collect()),
-                },
-                Codel {
-                    color: Color::Black,
-                    region: Region::new(vec![(1, 1)].into_iter().collect()),
-                },
-            ],
+            vec![(&Color::White, &white), (&Color::White, &white)],
+            vec![(&Color::White, &white), (&Color::Black, &black)],
         ];
-        assert_eq!(program.points, expected);
+        assert_eq!(actual, expected);
+        assert_eq!(program.regions().len(), 2);
+    }
+
+    #[test]
+    fn test_exits() {
+        let colors = vec![
+            vec![Color::White, Color::White],
+            vec![Color::White, Color::Black],
+        ];
+        let program = Program::new(colors, 2, 2);
+
+        let white = program.region_at((0, 0));
+        assert_eq!(
+            white.exit(Direction::Right, Chooser::Left),
+            &Exit {
+                codel: (0, 1),
+                next: None
+            }
+        );
+        assert_eq!(
+            white.exit(Direction::Down, Chooser::Right),
+            &Exit {
+                codel: (1, 0),
+                next: None
+            }
+        );
+
+        let black = program.region_at((1, 1));
+        assert_eq!(
+            black.exit(Direction::Up, Chooser::Left),
+            &Exit {
+                codel: (1, 1),
+                next: Some(((0, 1), Color::White))
+            }
+        );
+        assert_eq!(
+            black.exit(Direction::Left, Chooser::Right),
+            &Exit {
+                codel: (1, 1),
+                next: Some(((1, 0), Color::White))
+            }
+        );
+        assert_eq!(black.exits.len(), 8);
     }
 
     #[test]
@@ -318,12 +410,14 @@ mod test_program {
             vec![Color::White, Color::Black],
         ];
         let program = Program::new(colors, 2, 2);
-        assert_eq!(
-            program.region_at((1, 1)),
-            &Region {
-                members: vec![(1, 1)].into_iter().collect(),
-                size: 1
-            }
-        );
+        let region = program.region_at((1, 1));
+        assert_eq!(region.members, vec![(1, 1)].into_iter().collect());
+        assert_eq!(region.size, 1);
+        assert_eq!(region.color, Color::Black);
+        assert_eq!(region.bounds, ((1, 1), (1, 1)));
+        assert!(std::ptr::eq(
+            program.region_at((0, 0)),
+            program.region_at((1, 0))
+        ));
     }
 }
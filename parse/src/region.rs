@@ -1,49 +1,107 @@
-use std::collections::HashSet;
-
-use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
 
+use crate::chooser::Chooser;
+use crate::color::Color;
 use crate::direction::Direction;
 
+/// The way out of a region for a given direction pointer and codel chooser.
+///
+/// # Parameters
+///
+/// * `codel` - The (row, column) coordinates of the codel that the region is exited from.
+/// * `next` - The coordinates and color of the codel on the other side of the exit, if it's within the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exit {
+    pub codel: (usize, usize),
+    pub next: Option<((usize, usize), Color)>,
+}
+
+/// An index into a program's region arena.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RegionId(pub(crate) usize);
+
+/// A contiguous block of same-colored codels.
+///
+/// # Parameters
+///
+/// * `members` - The (row, column) coordinates of every codel in the region.
+/// * `size` - The number of codels in the region.
+/// * `color` - The color shared by every codel in the region.
+/// * `bounds` - The (row, column) coordinates of the top-left and bottom-right corners of the region's bounding box.
+/// * `exits` - The exit for each combination of direction pointer and codel chooser.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Region {
     pub(crate) members: HashSet<(usize, usize)>,
     pub size: usize,
+    pub color: Color,
+    pub bounds: ((usize, usize), (usize, usize)),
+    pub(crate) exits: HashMap<(Direction, Chooser), Exit>,
 }
 
 impl Region {
-    pub fn new(members: HashSet<(usize, usize)>) -> Region {
+    pub fn new(
+        members: HashSet<(usize, usize)>,
+        color: Color,
+        exits: HashMap<(Direction, Chooser), Exit>,
+    ) -> Region {
         let size = members.len();
-        Region { members, size }
+        let bounds = Self::bounds(&members);
+        Region {
+            members,
+            size,
+            color,
+            bounds,
+            exits,
+        }
     }
 
-    /// The coordinate of the farthest region edge (exclusive) reached starting from `start` and moving in `direction`.
-    /// TODO: these edges should probably be pre-computed
-    pub fn edge(&self, start: (usize, usize), direction: Direction) -> (usize, usize) {
-        let (row, col) = start;
-        match direction {
-            Direction::Up => (**self.codels_in_col(col).first().unwrap(), col),
-            Direction::Down => (**self.codels_in_col(col).last().unwrap(), col),
-            Direction::Left => (row, **self.codels_in_row(row).first().unwrap()),
-            Direction::Right => (row, **self.codels_in_row(row).last().unwrap()),
-        }
+    /// The top-left and bottom-right corners of the smallest box containing all members.
+    fn bounds(members: &HashSet<(usize, usize)>) -> ((usize, usize), (usize, usize)) {
+        let rows = members.iter().map(|(row, _)| *row);
+        let cols = members.iter().map(|(_, col)| *col);
+        (
+            (
+                rows.clone().min().unwrap_or(0),
+                cols.clone().min().unwrap_or(0),
+            ),
+            (rows.max().unwrap_or(0), cols.max().unwrap_or(0)),
+        )
     }
 
-    /// The rows of all codels in the same column in ascending order.
-    fn codels_in_col(&self, col: usize) -> Vec<&usize> {
-        self.members
-            .iter()
-            .filter_map(|(p_row, p_col)| if p_col == &col { Some(p_row) } else { None })
-            .sorted()
-            .collect()
+    /// The pre-computed exit taken when moving in `direction` with the chooser set to `chooser`.
+    pub fn exit(&self, direction: Direction, chooser: Chooser) -> &Exit {
+        &self.exits[&(direction, chooser)]
     }
 
-    /// The columns of all codels in the same row in ascending order.
-    fn codels_in_row(&self, row: usize) -> Vec<&usize> {
-        self.members
+    /// The codel that a region is exited from when moving in `direction` with the chooser pointing towards `chooser`.
+    ///
+    /// This is the codel on the region's furthest edge in `direction` that is itself furthest along that edge in `chooser`.
+    /// The edge spans the whole region, so it needn't share a row or column with the current pointer.
+    pub(crate) fn exit_codel(
+        members: &HashSet<(usize, usize)>,
+        direction: Direction,
+        chooser: Direction,
+    ) -> (usize, usize) {
+        *members
             .iter()
-            .filter_map(|(p_row, p_col)| if p_row == &row { Some(p_col) } else { None })
-            .sorted()
-            .collect()
+            .max_by_key(|point| {
+                (
+                    Self::distance(point, direction),
+                    Self::distance(point, chooser),
+                )
+            })
+            .unwrap()
+    }
+
+    /// How far a point is along `direction`, such that larger values are further in that direction.
+    fn distance(point: &(usize, usize), direction: Direction) -> isize {
+        let (row, col) = *point;
+        match direction {
+            Direction::Up => -(row as isize),
+            Direction::Down => row as isize,
+            Direction::Left => -(col as isize),
+            Direction::Right => col as isize,
+        }
     }
 }
 
@@ -52,46 +110,65 @@ mod test_region {
     use super::*;
 
     #[test]
-    fn test_codels_in_row() {
-        let region = Region::new(vec![(0, 0), (0, 1), (0, 2), (1, 0)].into_iter().collect());
-        assert_eq!(region.codels_in_row(0), vec![&0, &1, &2]);
-        assert_eq!(region.codels_in_row(1), vec![&0]);
+    fn test_distance() {
+        assert_eq!(Region::distance(&(1, 2), Direction::Up), -1);
+        assert_eq!(Region::distance(&(1, 2), Direction::Down), 1);
+        assert_eq!(Region::distance(&(1, 2), Direction::Left), -2);
+        assert_eq!(Region::distance(&(1, 2), Direction::Right), 2);
     }
 
     #[test]
-    fn test_codels_in_col() {
-        let region = Region::new(vec![(0, 0), (0, 1), (1, 0), (2, 0)].into_iter().collect());
-        assert_eq!(region.codels_in_col(0), vec![&0, &1, &2]);
-        assert_eq!(region.codels_in_col(1), vec![&0]);
+    fn test_bounds() {
+        let members = vec![(1, 2), (2, 1), (2, 2), (3, 2)].into_iter().collect();
+        assert_eq!(Region::bounds(&members), ((1, 1), (3, 2)));
     }
 
     #[test]
-    fn test_edge() {
-        let region = Region::new(
-            vec![
-                (0, 0),
-                (0, 1),
-                (0, 2),
-                (1, 0),
-                (1, 2),
-                (2, 0),
-                (2, 1),
-                (2, 2),
-            ]
+    fn test_exit_codel_ring() {
+        // ###
+        // # #
+        // ###
+        let members = vec![
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 2),
+            (2, 0),
+            (2, 1),
+            (2, 2),
+        ]
+        .into_iter()
+        .collect();
+        let exit = |direction, chooser| Region::exit_codel(&members, direction, chooser);
+
+        assert_eq!(exit(Direction::Right, Direction::Up), (0, 2));
+        assert_eq!(exit(Direction::Right, Direction::Down), (2, 2));
+        assert_eq!(exit(Direction::Down, Direction::Right), (2, 2));
+        assert_eq!(exit(Direction::Down, Direction::Left), (2, 0));
+        assert_eq!(exit(Direction::Left, Direction::Down), (2, 0));
+        assert_eq!(exit(Direction::Left, Direction::Up), (0, 0));
+        assert_eq!(exit(Direction::Up, Direction::Left), (0, 0));
+        assert_eq!(exit(Direction::Up, Direction::Right), (0, 2));
+    }
+
+    #[test]
+    fn test_exit_codel_l_shape() {
+        // #
+        // #
+        // ###
+        let members = vec![(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]
             .into_iter()
-            .collect(),
-        );
-
-        // Not disjoint
-        assert_eq!(region.edge((0, 0), Direction::Down), (2, 0));
-        assert_eq!(region.edge((0, 0), Direction::Right), (0, 2));
-        assert_eq!(region.edge((2, 2), Direction::Up), (0, 2));
-        assert_eq!(region.edge((2, 2), Direction::Left), (2, 0));
-
-        // Disjoint
-        assert_eq!(region.edge((0, 1), Direction::Down), (2, 1));
-        assert_eq!(region.edge((1, 0), Direction::Right), (1, 2));
-        assert_eq!(region.edge((2, 1), Direction::Up), (0, 1));
-        assert_eq!(region.edge((1, 2), Direction::Left), (1, 0));
+            .collect();
+        let exit = |direction, chooser| Region::exit_codel(&members, direction, chooser);
+
+        assert_eq!(exit(Direction::Right, Direction::Up), (2, 2));
+        assert_eq!(exit(Direction::Right, Direction::Down), (2, 2));
+        assert_eq!(exit(Direction::Down, Direction::Right), (2, 2));
+        assert_eq!(exit(Direction::Down, Direction::Left), (2, 0));
+        assert_eq!(exit(Direction::Left, Direction::Down), (2, 0));
+        assert_eq!(exit(Direction::Left, Direction::Up), (0, 0));
+        assert_eq!(exit(Direction::Up, Direction::Left), (0, 0));
+        assert_eq!(exit(Direction::Up, Direction::Right), (0, 0));
     }
 }
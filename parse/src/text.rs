@@ -0,0 +1,223 @@
+use crate::color::Color;
+use crate::error::ParseError;
+use crate::program::Program;
+
+impl Program {
+    /// Builds a program from ascii-piet text.
+    pub fn from_text(text: &str) -> Result<Program, ParseError> {
+        let colors = parse(text)?;
+        let (rows, cols) = (colors.len(), colors[0].len());
+        Ok(Self::new(colors, rows, cols))
+    }
+
+    /// Writes the program as ascii-piet text with one line per row.
+    pub fn to_text(&self) -> String {
+        (0..self.rows())
+            .map(|row| {
+                (0..self.cols())
+                    .map(|col| to_char(self.color_at((row, col))))
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+}
+
+/// Parses ascii-piet text into rows of colors, checking that every row is the same length.
+///
+/// The encoding is described at <https://github.com/dloscutoff/ascii-piet>.
+/// Every codel is a single letter:
+///
+/// | hue/lightness | red | yellow | green | cyan | blue | magenta |
+/// |---------------|-----|--------|-------|------|------|---------|
+/// | light         | `a` | `b`    | `c`   | `d`  | `e`  | `f`     |
+/// | neutral       | `g` | `h`    | `i`   | `j`  | `k`  | `l`     |
+/// | dark          | `m` | `n`    | `o`   | `p`  | `q`  | `r`     |
+/// along with black (`s`) and white (`t`)
+///
+/// Rows are separated by newlines.
+/// A row can also be ended by writing its last codel in uppercase, which allows a whole program to fit on one line.
+/// Blank lines are rejected since they can't be told apart from a row that went missing, other than at the end of the text.
+pub fn parse(text: &str) -> Result<Vec<Vec<Color>>, ParseError> {
+    let mut rows: Vec<Vec<Color>> = Vec::new();
+
+    for (line, characters) in text.trim_end_matches(['\n', '\r']).lines().enumerate() {
+        if characters.is_empty() {
+            return Err(ParseError::BlankLine { line });
+        }
+        let mut current: Vec<Color> = Vec::new();
+        for character in characters.chars() {
+            let color =
+                from_char(character.to_ascii_lowercase()).ok_or(ParseError::InvalidCharacter {
+                    row: rows.len(),
+                    col: current.len(),
+                    character,
+                })?;
+            current.push(color);
+            if character.is_ascii_uppercase() {
+                rows.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.is_empty() {
+            rows.push(current);
+        }
+    }
+
+    let cols = rows.first().ok_or(ParseError::EmptyImage)?.len();
+    if let Some((row, found)) = rows
+        .iter()
+        .map(|colors| colors.len())
+        .enumerate()
+        .find(|(_, len)| len != &cols)
+    {
+        return Err(ParseError::UnevenRows {
+            row,
+            expected: cols,
+            found,
+        });
+    }
+    Ok(rows)
+}
+
+/// The color represented by a lowercase ascii-piet character.
+fn from_char(character: char) -> Option<Color> {
+    match character {
+        's' => Some(Color::Black),
+        't' => Some(Color::White),
+        'a'..='r' => {
+            let index = character as u8 - b'a';
+            Some(Color::Color {
+                hue: index % 6,
+                lightness: index / 6,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The lowercase ascii-piet character representing a color.
+fn to_char(color: &Color) -> char {
+    match color {
+        Color::Black => 's',
+        Color::White => 't',
+        Color::Color { hue, lightness } => (b'a' + lightness * 6 + hue) as char,
+    }
+}
+
+#[cfg(test)]
+mod test_text {
+    use super::*;
+
+    #[test]
+    fn test_char_round_trip() {
+        for character in "abcdefghijklmnopqrst".chars() {
+            assert_eq!(to_char(&from_char(character).unwrap()), character);
+        }
+        assert_eq!(from_char('u'), None);
+    }
+
+    #[test]
+    fn test_from_char() {
+        assert_eq!(
+            from_char('a'),
+            Some(Color::Color {
+                hue: 0,
+                lightness: 0
+            })
+        );
+        assert_eq!(
+            from_char('j'),
+            Some(Color::Color {
+                hue: 3,
+                lightness: 1
+            })
+        );
+        assert_eq!(
+            from_char('r'),
+            Some(Color::Color {
+                hue: 5,
+                lightness: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let colors = parse("ts\nst\n").unwrap();
+        assert_eq!(
+            colors,
+            vec![
+                vec![Color::White, Color::Black],
+                vec![Color::Black, Color::White]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_single_line() {
+        assert_eq!(parse("tSsT").unwrap(), parse("ts\nst").unwrap());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(
+            parse("ts\nsx"),
+            Err(ParseError::InvalidCharacter {
+                row: 1,
+                col: 1,
+                character: 'x'
+            })
+        ));
+        assert!(matches!(
+            parse("ts\ns"),
+            Err(ParseError::UnevenRows {
+                row: 1,
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(parse("\n"), Err(ParseError::EmptyImage)));
+    }
+
+    #[test]
+    fn test_parse_blank_lines() {
+        assert!(matches!(
+            parse("ts\n\nst\n"),
+            Err(ParseError::BlankLine { line: 1 })
+        ));
+        assert!(matches!(
+            parse("\nts"),
+            Err(ParseError::BlankLine { line: 0 })
+        ));
+        assert_eq!(parse("ts\r\nst\r\n\n").unwrap(), parse("ts\nst").unwrap());
+    }
+
+    #[test]
+    fn test_parse_fixture() {
+        // Pushes 5 (the size of the light red region), prints it as a number, then is trapped in dark magenta
+        let text = include_str!("../fixtures/print_five.txt");
+        let (a, g, r, s) = (
+            from_char('a').unwrap(),
+            from_char('g').unwrap(),
+            from_char('r').unwrap(),
+            Color::Black,
+        );
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                vec![a.clone(), a.clone(), a.clone(), a.clone(), a, g, r.clone()],
+                vec![s.clone(), s.clone(), s.clone(), s, r.clone(), r.clone(), r],
+            ]
+        );
+        assert_eq!(parse("aaaaagRssssrrR").unwrap(), parse(text).unwrap());
+        assert_eq!(Program::from_text(text).unwrap().to_text(), text);
+    }
+
+    #[test]
+    fn test_program_round_trip() {
+        let text = "abct\ngsst\n";
+        let program = Program::from_text(text).unwrap();
+        assert_eq!((program.rows(), program.cols()), (2, 4));
+        assert_eq!(program.to_text(), text);
+    }
+}
@@ -0,0 +1,115 @@
+use std::convert::TryFrom;
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::error::ParseError;
+use crate::options::SaveOptions;
+use crate::program::Program;
+
+impl Program {
+    /// Saves the program as an image given the options to save it with.
+    pub fn save(&self, path: &str, options: &SaveOptions) -> Result<(), ParseError> {
+        let img = self.to_image(options.codel_size)?;
+        match options.format {
+            Some(format) => img.save_with_format(path, format)?,
+            None => img.save(path)?,
+        }
+        Ok(())
+    }
+
+    /// Draws the program as an image where each codel is `codel_size` by `codel_size` pixels of its canonical color.
+    /// Fails if `codel_size` is zero or the image would be too large for its dimensions to fit in a `u32`.
+    pub fn to_image(&self, codel_size: u32) -> Result<RgbaImage, ParseError> {
+        let pixels = |codels: usize| {
+            u32::try_from(codels)
+                .ok()
+                .and_then(|codels| codels.checked_mul(codel_size))
+                .filter(|_| codel_size > 0)
+        };
+        let (width, height) = match (pixels(self.cols()), pixels(self.rows())) {
+            (Some(width), Some(height)) => (width, height),
+            _ => {
+                return Err(ParseError::InvalidCodelSize {
+                    codel_size,
+                    width: self.cols() as u32,
+                    height: self.rows() as u32,
+                })
+            }
+        };
+
+        Ok(ImageBuffer::from_fn(width, height, |x, y| {
+            let point = ((y / codel_size) as usize, (x / codel_size) as usize);
+            let (r, g, b) = self.color_at(point).to_rgb();
+            Rgba([r, g, b, 0xFF])
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test_writer {
+    use image::{DynamicImage, ImageFormat};
+
+    use super::*;
+    use crate::color::Color;
+    use crate::options::{CodelSize, LoadOptions};
+
+    fn program() -> Program {
+        let colors = vec![vec![
+            Color::White,
+            Color::Color {
+                hue: 1,
+                lightness: 2,
+            },
+        ]];
+        Program::new(colors, 1, 2)
+    }
+
+    #[test]
+    fn test_to_image() {
+        let img = program().to_image(2).unwrap();
+        assert_eq!(img.dimensions(), (4, 2));
+        assert_eq!(img.get_pixel(1, 1), &Rgba([0xFF, 0xFF, 0xFF, 0xFF]));
+        assert_eq!(img.get_pixel(2, 0), &Rgba([0xC0, 0xC0, 0x00, 0xFF]));
+        assert_eq!(img.get_pixel(3, 1), &Rgba([0xC0, 0xC0, 0x00, 0xFF]));
+        assert!(matches!(
+            program().to_image(0),
+            Err(ParseError::InvalidCodelSize { .. })
+        ));
+        // 2 columns of u32::MAX pixels each don't fit in a u32
+        assert!(matches!(
+            program().to_image(u32::MAX),
+            Err(ParseError::InvalidCodelSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_image_round_trip() {
+        let img = DynamicImage::ImageRgba8(program().to_image(3).unwrap());
+        let options = LoadOptions {
+            codel_size: CodelSize::Auto,
+            ..LoadOptions::default()
+        };
+        let loaded = Program::try_from_image_with_options(&img, &options).unwrap();
+        assert_eq!(loaded.to_text(), program().to_text());
+    }
+
+    #[test]
+    fn test_save() {
+        let path = std::env::temp_dir().join(format!("piet-test-save-{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        let options = SaveOptions {
+            codel_size: 2,
+            format: Some(ImageFormat::Png),
+        };
+        program().save(path, &options).unwrap();
+        let loaded = Program::load_with_options(
+            path,
+            &LoadOptions {
+                codel_size: CodelSize::Fixed(2),
+                ..LoadOptions::default()
+            },
+        );
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap().to_text(), program().to_text());
+    }
+}
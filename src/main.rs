@@ -1,21 +1,214 @@
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
+
 use clap::Clap;
 
 use interpret::interpreter::Interpreter;
+use interpret::options::{Dialect, InvalidNumberPolicy, OverflowPolicy, RunLimits};
+use interpret::step::ExitReason;
+use interpret::trace::Tracer;
+use parse::error::ParseError;
+use parse::options::{CodelSize, LoadOptions, TransparencyPolicy, UnknownColorPolicy};
 use parse::program::Program;
+use parse::region::RegionId;
+
+/// The encodings that a program can be read from.
+enum Format {
+    Image,
+    Ascii,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "image" => Ok(Format::Image),
+            "ascii" => Ok(Format::Ascii),
+            _ => Err(format!("expected `image` or `ascii`, got `{}`", s)),
+        }
+    }
+}
+
+/// Parses a number of seconds to wait, which must be finite and not negative.
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| {
+            format!(
+                "expected a finite, non-negative number of seconds, got `{}`",
+                s
+            )
+        })
+}
 
 #[derive(Clap)]
 struct Opts {
+    /// The image to run, or `-` to read it from stdin
     file: String,
+    /// How the program is encoded: `image` or ascii-piet text (`ascii`)
+    #[clap(long, default_value = "image")]
+    format: Format,
+    /// The width and height in pixels of a single codel, or `auto` to infer it from the image (the default)
+    #[clap(long)]
+    codel_size: Option<CodelSize>,
+    /// How to treat colors outside the Piet palette: `error` (the default), `white`, `black`, `nearest` or `nearest:<max distance>`
+    #[clap(long)]
+    unknown_color: Option<UnknownColorPolicy>,
+    /// How to treat fully transparent pixels: `error` (the default), `white` or `black`
+    #[clap(long)]
+    transparent: Option<TransparencyPolicy>,
+    /// Print the program as ascii-piet text instead of running it
+    #[clap(long)]
+    to_ascii: bool,
+    /// How to resolve ambiguities in the spec: `spec`, `npiet` or `legacy`
+    #[clap(long, default_value = "spec")]
+    dialect: Dialect,
+    /// What to do when a number can't be read from stdin (overriding the dialect): `ignore`, `zero` or `halt`
+    #[clap(long)]
+    invalid_number: Option<InvalidNumberPolicy>,
+    /// What to do when arithmetic overflows: `wrap`, `saturate`, `ignore` or `halt`
+    #[clap(long, default_value = "ignore")]
+    overflow: OverflowPolicy,
+    /// Stop the program once it's stuck repeating the same steps without any input or output
+    #[clap(long)]
+    detect_loops: bool,
+    /// Print every step to stderr in the same format as npiet's `-t` trace
+    #[clap(long)]
+    trace: bool,
+    /// Stop after this many steps
+    #[clap(long)]
+    max_steps: Option<u64>,
+    /// Stop once the stack holds more than this many values
+    #[clap(long)]
+    max_stack_depth: Option<usize>,
+    /// Stop once more than this many bytes have been written
+    #[clap(long)]
+    max_output_bytes: Option<usize>,
+    /// Stop after running for this many seconds
+    #[clap(long, parse(try_from_str = parse_timeout))]
+    timeout: Option<Duration>,
 }
 
 fn main() {
-    let mut buffer = String::new();
+    let opts: Opts = Opts::parse();
+    let image_only =
+        opts.codel_size.is_some() || opts.unknown_color.is_some() || opts.transparent.is_some();
+    if matches!(opts.format, Format::Ascii) && image_only {
+        eprintln!(
+            "error: `--codel-size`, `--unknown-color` and `--transparent` only apply to images"
+        );
+        std::process::exit(2);
+    }
+    let options = LoadOptions {
+        codel_size: opts.codel_size.unwrap_or(CodelSize::Auto),
+        unknown_color: opts.unknown_color.unwrap_or(UnknownColorPolicy::Error),
+        transparent: opts.transparent.unwrap_or(TransparencyPolicy::Error),
+    };
 
-    println!("reading a single line from stdin");
-    std::io::stdin().read_line(&mut buffer).unwrap();
+    let loaded = load(&opts, &options);
+    let program = loaded.unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    });
+    if opts.to_ascii {
+        print!("{}", program.to_text());
+        return;
+    }
+    let mut dialect = opts.dialect;
+    if let Some(invalid_number) = opts.invalid_number {
+        dialect.invalid_number = invalid_number;
+    }
+    let limits = RunLimits {
+        max_steps: opts.max_steps,
+        max_stack_depth: opts.max_stack_depth,
+        max_output_bytes: opts.max_output_bytes,
+        wall_clock_timeout: opts.timeout,
+    };
+    let mut interpreter = Interpreter::new(program)
+        .with_dialect(dialect)
+        .with_limits(limits)
+        .with_overflow(opts.overflow)
+        .with_loop_detection(opts.detect_loops);
+    let result = if opts.trace {
+        let mut tracer = Tracer::default();
+        interpreter.run_with(|interpreter, result| {
+            if let Some(trace) = tracer.trace(interpreter, result) {
+                eprint!("{}", trace);
+            }
+        })
+    } else {
+        interpreter.run()
+    };
+    let program = interpreter.program();
+    match &result.reason {
+        ExitReason::Halted => (),
+        ExitReason::Overflow { region, command } => eprintln!(
+            "error: {} overflowed after {} steps leaving the {}",
+            command,
+            result.steps,
+            describe(program, *region)
+        ),
+        ExitReason::Loop { regions } => {
+            eprintln!(
+                "error: stuck in a loop of {} steps after {} steps, passing through:",
+                regions.len(),
+                result.steps
+            );
+            // Blocked steps stay in the same region so only list each region once per visit
+            let mut visited = regions.clone();
+            visited.dedup();
+            for region in visited {
+                eprintln!("  the {}", describe(program, region));
+            }
+        }
+        reason => eprintln!("stopped after {} steps: {}", result.steps, reason),
+    }
+    std::process::exit(exit_code(&result.reason));
+}
 
-    let opts: Opts = Opts::parse();
-    let program = Program::load(&opts.file);
-    let mut interpreter = Interpreter::new(program, buffer.chars().collect());
-    interpreter.run();
+/// Describes a region well enough to find it in the image.
+fn describe(program: &Program, region: RegionId) -> String {
+    let region = program.region(region);
+    let ((top, left), (bottom, right)) = region.bounds;
+    format!(
+        "{} region spanning rows {}-{} and columns {}-{}",
+        region.color, top, bottom, left, right
+    )
+}
+
+/// The process exit code for each reason that a program can stop.
+/// 1 is reserved for programs that fail to load and 2 for invalid arguments.
+fn exit_code(reason: &ExitReason) -> i32 {
+    match reason {
+        ExitReason::Halted => 0,
+        ExitReason::StepLimit => 3,
+        ExitReason::StackLimit => 4,
+        ExitReason::OutputLimit => 5,
+        ExitReason::Timeout => 6,
+        ExitReason::InputExhausted => 7,
+        ExitReason::InvalidInput => 8,
+        ExitReason::Overflow { .. } => 9,
+        ExitReason::Loop { .. } => 10,
+    }
+}
+
+/// Loads the program from either its path or stdin (if the path is `-`).
+fn load(opts: &Opts, options: &LoadOptions) -> Result<Program, ParseError> {
+    match (&opts.format, opts.file.as_str()) {
+        (Format::Image, "-") => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)?;
+            Program::from_bytes_with_options(&bytes, None, options)
+        }
+        (Format::Image, path) => Program::load_with_options(path, options),
+        (Format::Ascii, "-") => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            Program::from_text(&text)
+        }
+        (Format::Ascii, path) => Program::from_text(&std::fs::read_to_string(path)?),
+    }
 }